@@ -8,12 +8,18 @@ struct Vertex {
     @location(0) position: vec3<f32>,
     @location(1) normal: vec3<f32>,
     @location(2) uv: vec2<f32>,
+    @location(3) atlas_rect: vec4<f32>,
+    @location(4) ao: f32,
+    @location(5) light: vec2<f32>,
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) normal: vec3<f32>,
     @location(2) uv: vec2<f32>,
+    @location(3) atlas_rect: vec4<f32>,
+    @location(4) ao: f32,
+    @location(5) light: vec2<f32>,
 }
 
 @vertex 
@@ -27,12 +33,33 @@ fn vertex(vertex: Vertex) -> VertexOutput {
 
     out.normal = vertex.normal;
     out.uv = vertex.uv;
+    out.atlas_rect = vertex.atlas_rect;
+    out.ao = vertex.ao;
+    out.light = vertex.light;
 
     return out;
 }
 
 @fragment
 fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
-    let color = textureSample(atlas_texture, atlas_sampler, in.uv);
+    // UVs are in block units, so merged faces repeat the texture once per block
+    let atlas_uv = mix(in.atlas_rect.xy, in.atlas_rect.zw, fract(in.uv));
+
+    var color = textureSample(atlas_texture, atlas_sampler, atlas_uv);
+
+#ifndef TRANSLUCENT
+    // Leave out the see-through parts of textures, like the gaps between the leaves of a plant
+    if color.a < 0.5 {
+        discard;
+    }
+#endif
+
+    // Fully occluded corners keep some brightness so they don't turn black
+    color = vec4(color.rgb * mix(0.4, 1.0, in.ao), color.a);
+
+    // Every light level is a bit darker than the one above it, the brighter of sky and block light wins
+    let light = max(in.light.x, in.light.y);
+    color = vec4(color.rgb * pow(0.8, (1.0 - light) * 15.0), color.a);
+
     return color;
-}
\ No newline at end of file
+}
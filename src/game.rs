@@ -5,7 +5,7 @@ use bevy::{
 use bevy_rapier3d::prelude::*;
 
 use crate::{
-    mob, state,
+    item, mob, state,
     world::{self, render::ChunkMaterial},
 };
 
@@ -16,6 +16,7 @@ impl Plugin for ExcavateManufacturateGamePlugin {
         app.add_plugins(RapierPhysicsPlugin::<NoUserData>::default())
             .add_plugins(MaterialPlugin::<ChunkMaterial>::default())
             .add_plugins((
+                item::ExcavateManufacturateItemPlugin,
                 mob::ExcavateManufacturateMobPlugin,
                 mob::player::ExcavateManufacturatePlayerPlugin,
                 world::ExcavateManufacturateWorldPlugin,
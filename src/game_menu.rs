@@ -7,10 +7,10 @@ use bevy_egui::{egui, EguiContexts};
 use crate::{
     mob::player::Player,
     state::{GameModeState, MenuState},
-    util::block_pos::BlockPos,
+    util::{block_pos::BlockPos, mesh::MeshingMode},
     world::{
         generation::GeneratedChunkTask,
-        render::{ChunkSpawnQueue, SpawnedChunks},
+        render::{ChunkMeshTask, ChunkMeshes, ChunkSpawnQueue, SpawnedChunks},
         render_distance::RenderDistance,
         world_access::ExcavateManufacturateWorld,
     },
@@ -33,6 +33,8 @@ fn game_menu_system(
     mut next_state: ResMut<NextState<MenuState>>,
     mut next_game_mode: ResMut<NextState<GameModeState>>,
     mut render_distance: ResMut<RenderDistance>,
+    mut meshing_mode: ResMut<MeshingMode>,
+    em_world: Res<ExcavateManufacturateWorld>,
     player_transform: Query<(&Transform, &BlockPos), With<Player>>,
 ) {
     egui::Window::new("Game Menu").show(contexts.ctx_mut(), |ui| {
@@ -42,11 +44,31 @@ fn game_menu_system(
             block_pos, translation.x, translation.y, translation.z,
         ));
 
+        let surface_height = em_world.surface_height(block_pos.x, block_pos.z);
+        ui.label(format!(
+            "Surface height: {}; biome: {}",
+            surface_height.map_or("none".to_owned(), |height| height.to_string()),
+            em_world.biome_at(*block_pos).parameters().name
+        ));
+
         let mut render_distance_chunks = render_distance.chunks();
         ui.add(egui::Slider::new(&mut render_distance_chunks, 2..=16).text("Render distance"));
 
         render_distance.set_to(render_distance_chunks as usize);
 
+        ui.horizontal(|ui| {
+            ui.label("Meshing:");
+
+            // Only write to the resource when the mode actually changes, every change rebuilds all chunks
+            let mut selected_meshing_mode = *meshing_mode;
+            ui.radio_value(&mut selected_meshing_mode, MeshingMode::Greedy, "Greedy");
+            ui.radio_value(&mut selected_meshing_mode, MeshingMode::Naive, "Naive");
+
+            if selected_meshing_mode != *meshing_mode {
+                *meshing_mode = selected_meshing_mode;
+            }
+        });
+
         if ui.button("Creative mode").clicked() {
             next_game_mode.set(GameModeState::Creative);
         }
@@ -60,14 +82,18 @@ fn game_menu_system(
     });
 }
 
+#[allow(clippy::too_many_arguments)]
 fn performance_metrics_system(
     mut contexts: EguiContexts,
     diagnostics: Res<DiagnosticsStore>,
     spawned_chunks: Res<SpawnedChunks>,
+    chunk_meshes: Res<ChunkMeshes>,
+    meshes: Res<Assets<Mesh>>,
     em_world: Res<ExcavateManufacturateWorld>,
     spawn_queue: Res<ChunkSpawnQueue>,
     entity_query: Query<()>,
     tasks_query: Query<(), With<GeneratedChunkTask>>,
+    mesh_tasks_query: Query<(), With<ChunkMeshTask>>,
 ) {
     egui::Window::new("Performance Metrics").show(contexts.ctx_mut(), |ui| {
         if let Some(fps) = diagnostics
@@ -80,7 +106,18 @@ fn performance_metrics_system(
         ui.label(format!("Entity count: {}", entity_query.iter().count()));
         ui.label(format!("Chunks stored: {}", em_world.total_chunk_count()));
         ui.label(format!("Chunk gen tasks: {}", tasks_query.iter().count()));
+        ui.label(format!(
+            "Chunk mesh tasks: {}",
+            mesh_tasks_query.iter().count()
+        ));
         ui.label(format!("Chunks rendered: {}", spawned_chunks.len()));
         ui.label(format!("Chunks queued to render: {}", spawn_queue.len()));
+
+        let chunk_vertex_count: usize = chunk_meshes
+            .values()
+            .filter_map(|handle| meshes.get(handle))
+            .map(|mesh| mesh.count_vertices())
+            .sum();
+        ui.label(format!("Chunk vertices: {}", chunk_vertex_count));
     });
 }
@@ -0,0 +1,22 @@
+pub mod item_names {
+    use std::borrow::Cow;
+
+    use crate::item::ItemName;
+
+    pub const STICK: ItemName = ItemName(Cow::Borrowed("excavatemanufacturate/stick"));
+    pub const WOODEN_SHOVEL: ItemName =
+        ItemName(Cow::Borrowed("excavatemanufacturate/wooden_shovel"));
+    pub const WOODEN_PICKAXE: ItemName =
+        ItemName(Cow::Borrowed("excavatemanufacturate/wooden_pickaxe"));
+    pub const STONE_PICKAXE: ItemName =
+        ItemName(Cow::Borrowed("excavatemanufacturate/stone_pickaxe"));
+}
+
+pub mod item_data {
+    use crate::{item::registry::StaticItemData, world::block::static_block_data::ToolType};
+
+    pub const STICK: StaticItemData = StaticItemData::material();
+    pub const WOODEN_SHOVEL: StaticItemData = StaticItemData::tool(ToolType::Shovel, 0);
+    pub const WOODEN_PICKAXE: StaticItemData = StaticItemData::tool(ToolType::Pickaxe, 0);
+    pub const STONE_PICKAXE: StaticItemData = StaticItemData::tool(ToolType::Pickaxe, 1);
+}
@@ -0,0 +1,28 @@
+use std::{borrow::Cow, num::NonZeroU16};
+
+use bevy::prelude::*;
+
+pub mod excavatemanufacturate_items;
+pub mod registry;
+
+pub struct ExcavateManufacturateItemPlugin;
+
+impl Plugin for ExcavateManufacturateItemPlugin {
+    fn build(&self, app: &mut App) {
+        // Block items are created from the block registry, so it needs to exist first
+        app.add_systems(
+            Startup,
+            registry::setup.after(crate::world::block::registry::setup),
+        );
+    }
+}
+
+/// A namespaced item name. Items that place a block share the name of that block.
+#[derive(Debug, Clone, Deref, DerefMut, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ItemName(pub Cow<'static, str>);
+
+/// Represents an identifier of static item data. Item stacks store this ID instead of the data itself.
+///
+/// Should never be constructed manually; this value is managed by the item registry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ItemId(pub(in crate::item) NonZeroU16);
@@ -0,0 +1,126 @@
+use std::{num::NonZeroU16, sync::Arc};
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::world::block::{
+    registry::BlockRegistryResource, static_block_data::ToolType, BlockId, BlockName,
+};
+
+use super::{excavatemanufacturate_items, ItemId, ItemName};
+
+/// The stack size of items that don't specify one.
+pub const DEFAULT_MAX_STACK_SIZE: u32 = 64;
+
+pub struct StaticItemData {
+    pub max_stack_size: u32,
+
+    /// The tool type and tier of this item, if it's a tool.
+    pub tool: Option<(ToolType, u8)>,
+
+    /// The block this item places, if it's a block item.
+    pub places_block: Option<BlockId>,
+}
+
+impl StaticItemData {
+    /// An item that doesn't do anything by itself, such as a crafting material.
+    pub const fn material() -> Self {
+        Self {
+            max_stack_size: DEFAULT_MAX_STACK_SIZE,
+            tool: None,
+            places_block: None,
+        }
+    }
+
+    /// A tool of the given type and tier. Tools don't stack.
+    pub const fn tool(tool_type: ToolType, tier: u8) -> Self {
+        Self {
+            max_stack_size: 1,
+            tool: Some((tool_type, tier)),
+            places_block: None,
+        }
+    }
+
+    /// An item that places the block.
+    pub const fn block(block_id: BlockId) -> Self {
+        Self {
+            max_stack_size: DEFAULT_MAX_STACK_SIZE,
+            tool: None,
+            places_block: Some(block_id),
+        }
+    }
+}
+
+#[derive(Resource, Deref)]
+pub struct ItemRegistryResource(Arc<ItemRegistry>);
+
+pub struct ItemRegistry {
+    pub item_ids: HashMap<ItemName, ItemId>,
+    pub item_names: HashMap<ItemId, ItemName>,
+    pub static_item_data: HashMap<ItemId, StaticItemData>,
+}
+
+impl ItemRegistry {
+    fn create(block_ids: &HashMap<BlockName, BlockId>) -> Self {
+        use excavatemanufacturate_items::*;
+
+        let mut registry = Self {
+            item_ids: HashMap::new(),
+            item_names: HashMap::new(),
+            static_item_data: HashMap::new(),
+        };
+
+        // Every block gets an item, sorted so item IDs don't depend on hash map order
+        let mut blocks: Vec<_> = block_ids.iter().collect();
+        blocks.sort_by_key(|(_, &block_id)| block_id);
+
+        for (block_name, &block_id) in blocks {
+            registry.register(
+                ItemName(block_name.0.clone()),
+                StaticItemData::block(block_id),
+            );
+        }
+
+        registry.register(item_names::STICK, item_data::STICK);
+        registry.register(item_names::WOODEN_SHOVEL, item_data::WOODEN_SHOVEL);
+        registry.register(item_names::WOODEN_PICKAXE, item_data::WOODEN_PICKAXE);
+        registry.register(item_names::STONE_PICKAXE, item_data::STONE_PICKAXE);
+
+        registry
+    }
+
+    fn register(&mut self, name: ItemName, static_item_data: StaticItemData) -> ItemId {
+        assert!(
+            !self.item_ids.contains_key(&name),
+            "Item {:?} is registered twice",
+            name
+        );
+
+        let id = ItemId(NonZeroU16::new(self.item_ids.len() as u16 + 1).unwrap());
+
+        self.item_ids.insert(name.clone(), id);
+        self.item_names.insert(id, name);
+        self.static_item_data.insert(id, static_item_data);
+
+        id
+    }
+
+    pub fn get_item_id(&self, name: &ItemName) -> Option<ItemId> {
+        self.item_ids.get(name).cloned()
+    }
+
+    pub fn get_item_name(&self, id: ItemId) -> &ItemName {
+        &self.item_names[&id]
+    }
+
+    pub fn get_item_data(&self, id: ItemId) -> &StaticItemData {
+        // It's ok to panic here because ItemId is never manually created; it should always be valid.
+        self.static_item_data
+            .get(&id)
+            .unwrap_or_else(|| panic!("Item id {:?} doesn't exist in the item registry", id))
+    }
+}
+
+pub fn setup(mut commands: Commands, block_registry: Res<BlockRegistryResource>) {
+    let item_registry = ItemRegistry::create(&block_registry.block_ids);
+    commands.insert_resource(ItemRegistryResource(Arc::new(item_registry)));
+}
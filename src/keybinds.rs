@@ -14,6 +14,9 @@ pub struct Keybinds {
     pub break_block: MouseButton,
     pub place_block: MouseButton,
 
+    /// Selects the hotbar slot at the same index
+    pub hotbar: [KeyCode; 9],
+
     // Menu
     pub pause: KeyCode,
     pub exit: KeyCode,
@@ -34,6 +37,10 @@ impl Default for Keybinds {
             break_block: MouseButton::Left,
             place_block: MouseButton::Right,
 
+            hotbar: [
+                Digit1, Digit2, Digit3, Digit4, Digit5, Digit6, Digit7, Digit8, Digit9,
+            ],
+
             pause: Escape,
             exit: Backspace,
         }
@@ -3,9 +3,11 @@ use state::{GameModeState, MenuState, PlayState};
 
 mod game;
 mod game_menu;
+mod item;
 mod keybinds;
 mod main_menu;
 mod mob;
+mod mods;
 mod state;
 mod util;
 mod world;
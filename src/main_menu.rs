@@ -1,7 +1,11 @@
 use bevy::{app::AppExit, prelude::*};
 use bevy_egui::{egui, EguiContexts};
 
-use crate::{state::MenuState, world::render_distance::RenderDistance};
+use crate::{
+    mods::{ModInfo, Mods},
+    state::MenuState,
+    world::{render_distance::RenderDistance, save::SaveSettings},
+};
 
 pub struct ExcavateManufacturateMainMenuPlugin;
 
@@ -19,10 +23,34 @@ fn main_menu_system(
     mut next_state: ResMut<NextState<MenuState>>,
     mut app_exit_events: EventWriter<AppExit>,
     mut render_distance: ResMut<RenderDistance>,
+    mut save_settings: ResMut<SaveSettings>,
     input: Res<ButtonInput<KeyCode>>,
+    mods: Res<Mods>,
 ) {
-    egui::Window::new("Main Menu").show(contexts.ctx_mut(), |ui| {
-        if ui.button("Start").clicked() || input.just_pressed(KeyCode::Space) {
+    let ctx = contexts.ctx_mut();
+
+    // Keyboard shortcuts shouldn't trigger while typing in the world name or seed field
+    let shortcuts_enabled = !ctx.wants_keyboard_input();
+
+    egui::Window::new("Main Menu").show(ctx, |ui| {
+        ui.horizontal(|ui| {
+            ui.label("World name");
+            ui.text_edit_singleline(&mut save_settings.world_name);
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Seed");
+            ui.text_edit_singleline(&mut save_settings.seed)
+                .on_hover_text("Used when creating a new world. Leave empty for a random seed.");
+        });
+
+        let world_name_is_valid = is_valid_world_name(&save_settings.world_name);
+
+        if ui
+            .add_enabled(world_name_is_valid, egui::Button::new("Start"))
+            .clicked()
+            || (world_name_is_valid && shortcuts_enabled && input.just_pressed(KeyCode::Space))
+        {
             next_state.set(MenuState::InGame);
         }
 
@@ -31,8 +59,55 @@ fn main_menu_system(
 
         render_distance.set_to(render_distance_chunks as usize);
 
-        if ui.button("Exit").clicked() || input.just_pressed(KeyCode::Backspace) {
+        if ui.button("Exit").clicked()
+            || (shortcuts_enabled && input.just_pressed(KeyCode::Backspace))
+        {
             app_exit_events.send(AppExit);
         }
+
+        ui.separator();
+        mods_section(ui, &mods);
+    });
+}
+
+fn mods_section(ui: &mut egui::Ui, mods: &Mods) {
+    egui::CollapsingHeader::new(format!("Mods ({})", mods.loaded.len())).show(ui, |ui| {
+        for info in mods.loaded.iter() {
+            ui.label(format!(
+                "{} ({}): {} blocks, {} world generation features",
+                info.namespace,
+                mod_source(info),
+                info.blocks.len(),
+                info.world_gen_features
+            ));
+        }
     });
+
+    if !mods.conflicts.is_empty() {
+        ui.colored_label(
+            egui::Color32::YELLOW,
+            format!("{} mod conflicts:", mods.conflicts.len()),
+        );
+
+        for conflict in mods.conflicts.iter() {
+            ui.colored_label(egui::Color32::YELLOW, conflict.to_string());
+        }
+    }
+}
+
+fn mod_source(info: &ModInfo) -> &'static str {
+    match (info.is_built_in(), info.plugin, info.asset_folder.is_some()) {
+        (true, _, _) => "built in",
+        (false, true, true) => "plugin and asset folder",
+        (false, true, false) => "plugin",
+        (false, false, _) => "asset folder",
+    }
+}
+
+/// World names are used as directory names, so they're restricted to characters that are safe on every platform.
+fn is_valid_world_name(world_name: &str) -> bool {
+    !world_name.is_empty()
+        && world_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == ' ')
 }
@@ -5,36 +5,72 @@ use crate::{
     keybinds::Keybinds,
     util::{self, block_pos::BlockPos, raytrace::Hit},
     world::{
-        block::{registry::BlockRegistryResource, static_block_data::BlockHardnessLevel},
-        world_access::{BlockDestroyEvent, ExcavateManufacturateWorld},
+        block::{
+            registry::{BlockRegistry, BlockRegistryResource},
+            static_block_data::{BlockHardnessLevel, ToolType},
+            Block, BlockData, BlockId,
+        },
+        world_access::{BlockDestroyEvent, BlockPlaceEvent, ExcavateManufacturateWorld},
     },
 };
 
-use super::Player;
+use super::{Player, PlayerPhysics, PLAYER_HALF_EXTENTS};
 
 #[derive(Resource, Deref)]
 pub struct PlayerRaycast(pub Option<Hit>);
 
+/// The block type that the player places. `None` means the player has nothing to place.
+#[derive(Component, Default, Deref, DerefMut)]
+pub struct SelectedBlock(pub Option<BlockId>);
+
+/// The tool the player is holding, as a tool type and its tier. `None` means the player mines by hand.
+#[derive(Component, Default, Deref, DerefMut)]
+pub struct HeldTool(pub Option<(ToolType, u8)>);
+
+/// How far the player has gotten breaking a block in survival mode.
+#[derive(Resource, Default)]
+pub struct BlockBreakProgress {
+    target: Option<BlockPos>,
+
+    /// Seconds spent breaking the target so far.
+    elapsed: f32,
+
+    /// Between 0 and 1, where 1 means the block breaks.
+    progress: f32,
+}
+
+impl BlockBreakProgress {
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
 pub fn setup(mut commands: Commands) {
     commands.insert_resource(PlayerRaycast(None));
+    commands.init_resource::<BlockBreakProgress>();
 }
 
 pub fn cleanup(mut commands: Commands) {
     commands.remove_resource::<PlayerRaycast>();
+    commands.remove_resource::<BlockBreakProgress>();
 }
 
 pub fn raycast(
     player_transform: Query<&Transform, With<Player>>,
     em_world: Res<ExcavateManufacturateWorld>,
+    block_registry: Res<BlockRegistryResource>,
     mut player_raycast: ResMut<PlayerRaycast>,
 ) {
     let player_transform = player_transform.single();
 
+    let ray_pos = player_transform.translation;
+    let ray_dir = Vec3::from(player_transform.forward());
+
     player_raycast.0 = util::raytrace::raytrace_dda(
-        player_transform.translation,
-        Vec3::from(player_transform.forward()),
+        ray_pos,
+        ray_dir,
         30,
-        em_world.hit_evaluator(),
+        em_world.hit_evaluator(&block_registry, ray_pos, ray_dir),
     )
 }
 
@@ -44,7 +80,12 @@ pub fn draw_crosshair(player_raycast: Res<PlayerRaycast>, mut gizmos: Gizmos) {
     }
 }
 
-pub fn handle_destroy_block(
+/// The position of the block the raycast hit.
+fn targeted_block_pos(hit: &Hit) -> BlockPos {
+    BlockPos::from(hit.voxel)
+}
+
+pub fn handle_destroy_block_creative(
     player_raycast: Res<PlayerRaycast>,
 
     mut block_destroy_events: EventWriter<BlockDestroyEvent>,
@@ -56,7 +97,7 @@ pub fn handle_destroy_block(
 ) {
     if let PlayerRaycast(Some(hit)) = *player_raycast {
         if input.just_pressed(keybinds.break_block) {
-            let block_pos = BlockPos::from(hit.position - 0.1 * hit.normal);
+            let block_pos = targeted_block_pos(&hit);
             let block_data = em_world.get_block(block_pos);
 
             let block_can_be_destroyed = block_data.is_some_and(|block_data| {
@@ -72,3 +113,195 @@ pub fn handle_destroy_block(
         }
     }
 }
+
+/// Breaks the targeted block once the break button was held for long enough. How long that takes depends on the
+/// block's hardness and the held tool, see [`BlockHardnessLevel::break_time`].
+#[allow(clippy::too_many_arguments)]
+pub fn handle_destroy_block_survival(
+    player_raycast: Res<PlayerRaycast>,
+    held_tool_query: Query<&HeldTool, With<Player>>,
+    mut break_progress: ResMut<BlockBreakProgress>,
+
+    mut block_destroy_events: EventWriter<BlockDestroyEvent>,
+    em_world: Res<ExcavateManufacturateWorld>,
+    block_registry: Res<BlockRegistryResource>,
+
+    input: Res<ButtonInput<MouseButton>>,
+    keybinds: Res<Keybinds>,
+    time: Res<Time>,
+) {
+    let PlayerRaycast(Some(hit)) = *player_raycast else {
+        break_progress.reset();
+        return;
+    };
+
+    if !input.pressed(keybinds.break_block) {
+        break_progress.reset();
+        return;
+    }
+
+    let block_pos = targeted_block_pos(&hit);
+
+    if break_progress.target != Some(block_pos) {
+        // Looking at a different block starts over
+        break_progress.reset();
+        break_progress.target = Some(block_pos);
+    }
+
+    let Some(Some(block_type)) = em_world
+        .get_block(block_pos)
+        .map(|block_data| block_data.as_ref())
+    else {
+        break_progress.reset();
+        return;
+    };
+
+    let hardness = block_registry.get_block_data(block_type.id).hardness;
+
+    let Some(break_time) = hardness.break_time(held_tool_query.single().0) else {
+        // The held tool doesn't meet the requirement, so the block never breaks
+        return;
+    };
+
+    break_progress.elapsed += time.delta_seconds();
+    break_progress.progress = (break_progress.elapsed / break_time).min(1.0);
+
+    if break_progress.progress >= 1.0 {
+        block_destroy_events.send(BlockDestroyEvent::create(block_pos, &em_world));
+        break_progress.reset();
+    }
+}
+
+pub fn draw_break_progress(break_progress: Res<BlockBreakProgress>, mut gizmos: Gizmos) {
+    let Some(block_pos) = break_progress.target else {
+        return;
+    };
+
+    if break_progress.progress <= 0.0 {
+        return;
+    }
+
+    // A box that grows from the center of the block until it covers the whole block
+    let block_center = block_pos.as_vec3() - 0.5;
+
+    gizmos.cuboid(
+        Transform::from_translation(block_center)
+            .with_scale(Vec3::splat(break_progress.progress * 1.01)),
+        Color::BLACK,
+    );
+}
+
+/// Whether a block at the position would overlap a player collider centered at the translation.
+fn block_intersects_player(block_pos: BlockPos, player_translation: Vec3) -> bool {
+    // A block at `pos` occupies the space between `pos - 1` and `pos`
+    let block_max = block_pos.as_vec3();
+    let block_min = block_max - 1.0;
+
+    let player_min = player_translation - PLAYER_HALF_EXTENTS;
+    let player_max = player_translation + PLAYER_HALF_EXTENTS;
+
+    block_min.cmplt(player_max).all() && block_max.cmpgt(player_min).all()
+}
+
+/// The state a block gets placed in. Blocks with an `axis` property line up with the face they're placed against,
+/// and blocks with a `facing` property face the player. Blocks with a `half` property go in the upper half of the
+/// block when they're placed against the underside of a block or the upper half of its side, like slabs.
+fn placement_state(
+    block_registry: &BlockRegistry,
+    block_id: BlockId,
+    hit: &Hit,
+    towards_player: Vec3,
+) -> BlockId {
+    let half =
+        if hit.normal.y < 0.0 || (hit.normal.y == 0.0 && hit.position.y.rem_euclid(1.0) > 0.5) {
+            "top"
+        } else {
+            "bottom"
+        };
+
+    let normal = hit.normal.abs();
+
+    let axis = if normal.x > normal.y && normal.x > normal.z {
+        "x"
+    } else if normal.y > normal.z {
+        "y"
+    } else {
+        "z"
+    };
+
+    let facing = if towards_player.x.abs() > towards_player.z.abs() {
+        if towards_player.x > 0.0 {
+            "east"
+        } else {
+            "west"
+        }
+    } else if towards_player.z > 0.0 {
+        "south"
+    } else {
+        "north"
+    };
+
+    let block_id = block_registry
+        .with_property(block_id, "axis", axis)
+        .unwrap_or(block_id);
+
+    let block_id = block_registry
+        .with_property(block_id, "facing", facing)
+        .unwrap_or(block_id);
+
+    block_registry
+        .with_property(block_id, "half", half)
+        .unwrap_or(block_id)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn handle_place_block(
+    player_raycast: Res<PlayerRaycast>,
+    player_physics_query: Query<&Transform, With<PlayerPhysics>>,
+    selected_block_query: Query<&SelectedBlock, With<Player>>,
+    block_registry: Res<BlockRegistryResource>,
+
+    mut block_place_events: EventWriter<BlockPlaceEvent>,
+    em_world: Res<ExcavateManufacturateWorld>,
+
+    input: Res<ButtonInput<MouseButton>>,
+    keybinds: Res<Keybinds>,
+) {
+    if let PlayerRaycast(Some(hit)) = *player_raycast {
+        if input.just_pressed(keybinds.place_block) {
+            // Step out of the hit block, into the block in front of the face that was hit
+            let block_pos = BlockPos::from(hit.voxel + hit.normal.as_ivec3());
+
+            // Only place into empty space in generated chunks, including chunks of nothing but air
+            if !em_world
+                .get_block(block_pos)
+                .is_some_and(|block_data| block_data.is_none())
+            {
+                return;
+            }
+
+            let player_translation = player_physics_query.single().translation;
+            if block_intersects_player(block_pos, player_translation) {
+                return;
+            }
+
+            let SelectedBlock(Some(block_id)) = *selected_block_query.single() else {
+                return;
+            };
+
+            block_place_events.send(BlockPlaceEvent {
+                pos: block_pos,
+                block: BlockData::some(Block {
+                    id: placement_state(
+                        &block_registry,
+                        block_id,
+                        &hit,
+                        // A block at `pos` is centered at `pos - 0.5`
+                        player_translation - (block_pos.as_vec3() - 0.5),
+                    ),
+                    dynamic_data: None,
+                }),
+            });
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use bevy::{
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    prelude::*,
+};
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    item::{
+        excavatemanufacturate_items::item_names,
+        registry::{ItemRegistry, ItemRegistryResource},
+        ItemId, ItemName,
+    },
+    keybinds::Keybinds,
+    world::block::excavatemanufacturate_blocks::block_names,
+};
+
+use super::{
+    interact::{HeldTool, SelectedBlock},
+    Player,
+};
+
+/// The number of slots in the hotbar. The hotbar is made up of the first slots of the inventory.
+pub const HOTBAR_SIZE: usize = 9;
+
+/// The total number of slots in the player's inventory, including the hotbar.
+pub const PLAYER_INVENTORY_SIZE: usize = HOTBAR_SIZE * 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ItemStack {
+    pub item: ItemId,
+    pub count: u32,
+}
+
+impl ItemStack {
+    pub fn new(item: ItemId, count: u32) -> Self {
+        Self { item, count }
+    }
+}
+
+/// A fixed amount of slots that each hold an item stack. The first [`HOTBAR_SIZE`] slots make up the hotbar, one of
+/// which is selected.
+#[derive(Component)]
+pub struct Inventory {
+    slots: Vec<Option<ItemStack>>,
+    selected_slot: usize,
+}
+
+impl Inventory {
+    pub fn new(size: usize) -> Self {
+        assert!(
+            size >= HOTBAR_SIZE,
+            "An inventory needs at least {} slots for the hotbar",
+            HOTBAR_SIZE
+        );
+
+        Self {
+            slots: vec![None; size],
+            selected_slot: 0,
+        }
+    }
+
+    pub fn get(&self, slot: usize) -> Option<&ItemStack> {
+        self.slots.get(slot).and_then(Option::as_ref)
+    }
+
+    pub fn selected_slot(&self) -> usize {
+        self.selected_slot
+    }
+
+    /// Selects a hotbar slot. Slots outside of the hotbar are ignored.
+    pub fn select(&mut self, slot: usize) {
+        if slot < HOTBAR_SIZE {
+            self.selected_slot = slot;
+        }
+    }
+
+    /// Moves the selection by the given amount of slots, wrapping around the ends of the hotbar.
+    pub fn scroll_selection(&mut self, delta: i32) {
+        self.selected_slot =
+            (self.selected_slot as i32 + delta).rem_euclid(HOTBAR_SIZE as i32) as usize;
+    }
+
+    pub fn selected_stack(&self) -> Option<&ItemStack> {
+        self.get(self.selected_slot)
+    }
+
+    /// Inserts a stack, first topping up stacks of the same item and then filling empty slots. Returns whatever
+    /// didn't fit into the inventory.
+    pub fn insert(
+        &mut self,
+        mut stack: ItemStack,
+        item_registry: &ItemRegistry,
+    ) -> Option<ItemStack> {
+        let max_stack_size = item_registry.get_item_data(stack.item).max_stack_size;
+
+        for existing in self.slots.iter_mut().flatten() {
+            if stack.count == 0 {
+                return None;
+            }
+
+            if existing.item == stack.item && existing.count < max_stack_size {
+                let moved = stack.count.min(max_stack_size - existing.count);
+                existing.count += moved;
+                stack.count -= moved;
+            }
+        }
+
+        for slot in self.slots.iter_mut() {
+            if stack.count == 0 {
+                return None;
+            }
+
+            if slot.is_none() {
+                let moved = stack.count.min(max_stack_size);
+                *slot = Some(ItemStack::new(stack.item, moved));
+                stack.count -= moved;
+            }
+        }
+
+        (stack.count > 0).then_some(stack)
+    }
+
+    /// Removes up to `count` items from the slot, returning the removed items.
+    pub fn remove(&mut self, slot: usize, count: u32) -> Option<ItemStack> {
+        let existing = self.slots.get_mut(slot)?;
+        let stack = existing.as_mut()?;
+
+        let item = stack.item;
+        let removed = count.min(stack.count);
+        stack.count -= removed;
+
+        if stack.count == 0 {
+            *existing = None;
+        }
+
+        (removed > 0).then_some(ItemStack::new(item, removed))
+    }
+
+    /// Moves the stack in one slot to another. Stacks of the same item are merged as far as the stack size allows,
+    /// otherwise the two stacks swap places.
+    pub fn move_stack(&mut self, from: usize, to: usize, item_registry: &ItemRegistry) {
+        if from == to || from >= self.slots.len() || to >= self.slots.len() {
+            return;
+        }
+
+        match (self.slots[from], self.slots[to]) {
+            (Some(source), Some(mut target)) if source.item == target.item => {
+                let max_stack_size = item_registry.get_item_data(target.item).max_stack_size;
+                let moved = source
+                    .count
+                    .min(max_stack_size.saturating_sub(target.count));
+                target.count += moved;
+
+                self.slots[to] = Some(target);
+                self.slots[from] = (source.count > moved)
+                    .then_some(ItemStack::new(source.item, source.count - moved));
+            }
+            _ => self.slots.swap(from, to),
+        }
+    }
+}
+
+/// The inventory a new player starts out with.
+pub fn starting_inventory(item_registry: &ItemRegistry) -> Inventory {
+    let mut inventory = Inventory::new(PLAYER_INVENTORY_SIZE);
+
+    for (name, count) in [
+        (ItemName(block_names::STONE.0), 64),
+        (ItemName(block_names::DIRT.0), 64),
+        (ItemName(block_names::GRASS.0), 64),
+        (ItemName(block_names::LOG.0), 64),
+        (ItemName(block_names::LEAVES.0), 64),
+        (ItemName(block_names::FURNACE.0), 64),
+        (ItemName(block_names::STONE_SLAB.0), 64),
+        (ItemName(block_names::STONE_STAIRS.0), 64),
+        (ItemName(block_names::GLASS.0), 64),
+        (ItemName(block_names::TALL_GRASS.0), 64),
+        (ItemName(block_names::LAMP.0), 64),
+        (item_names::WOODEN_PICKAXE, 1),
+        (item_names::STONE_PICKAXE, 1),
+        (item_names::WOODEN_SHOVEL, 1),
+        (item_names::STICK, 16),
+    ] {
+        let Some(item) = item_registry.get_item_id(&name) else {
+            warn!("Starting item {:?} doesn't exist", name);
+            continue;
+        };
+
+        inventory.insert(ItemStack::new(item, count), item_registry);
+    }
+
+    inventory
+}
+
+pub fn select_hotbar_slot(
+    mut inventory_query: Query<&mut Inventory, With<Player>>,
+    mut mouse_wheel_events: EventReader<MouseWheel>,
+    input: Res<ButtonInput<KeyCode>>,
+    keybinds: Res<Keybinds>,
+) {
+    let mut inventory = inventory_query.single_mut();
+
+    for (slot, &key) in keybinds.hotbar.iter().enumerate() {
+        if input.just_pressed(key) {
+            inventory.select(slot);
+        }
+    }
+
+    let mut scroll = 0.0;
+
+    for event in mouse_wheel_events.read() {
+        scroll += match event.unit {
+            MouseScrollUnit::Line => event.y,
+            // Touchpads report many small pixel deltas, only count the direction
+            MouseScrollUnit::Pixel => event.y.signum(),
+        };
+    }
+
+    if scroll != 0.0 {
+        // Scrolling up moves the selection to the left
+        inventory.scroll_selection(-scroll.signum() as i32);
+    }
+}
+
+/// Places the block and mines with the tool in the selected hotbar slot.
+pub fn update_held_item(
+    mut player_query: Query<(&Inventory, &mut SelectedBlock, &mut HeldTool), With<Player>>,
+    item_registry: Res<ItemRegistryResource>,
+) {
+    let (inventory, mut selected_block, mut held_tool) = player_query.single_mut();
+
+    let item_data = inventory
+        .selected_stack()
+        .map(|stack| item_registry.get_item_data(stack.item));
+
+    // Anything that isn't a tool mines like a bare hand
+    selected_block.0 = item_data.and_then(|item_data| item_data.places_block);
+    held_tool.0 = item_data.and_then(|item_data| item_data.tool);
+}
+
+pub fn draw_hotbar(
+    mut contexts: EguiContexts,
+    inventory_query: Query<&Inventory, With<Player>>,
+    item_registry: Res<ItemRegistryResource>,
+) {
+    let inventory = inventory_query.single();
+
+    egui::Area::new(egui::Id::new("hotbar"))
+        .anchor(egui::Align2::CENTER_BOTTOM, egui::vec2(0.0, -16.0))
+        .show(contexts.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                for slot in 0..HOTBAR_SIZE {
+                    let stroke = if slot == inventory.selected_slot() {
+                        egui::Stroke::new(2.0, egui::Color32::WHITE)
+                    } else {
+                        egui::Stroke::new(1.0, egui::Color32::DARK_GRAY)
+                    };
+
+                    egui::Frame::none()
+                        .fill(egui::Color32::from_black_alpha(160))
+                        .stroke(stroke)
+                        .inner_margin(4.0)
+                        .show(ui, |ui| {
+                            ui.set_min_size(egui::vec2(56.0, 40.0));
+
+                            ui.vertical_centered(|ui| match inventory.get(slot) {
+                                Some(stack) => {
+                                    let name = item_registry.get_item_name(stack.item);
+
+                                    // Leave out the namespace
+                                    ui.label(name.rsplit('/').next().unwrap_or(name));
+
+                                    if stack.count > 1 {
+                                        ui.label(stack.count.to_string());
+                                    }
+                                }
+                                None => {
+                                    ui.label("");
+                                }
+                            });
+                        });
+                }
+            });
+        });
+}
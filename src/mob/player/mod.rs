@@ -3,6 +3,7 @@ use bevy_rapier3d::prelude::*;
 use rand::Rng;
 
 use crate::{
+    item::registry::ItemRegistryResource,
     state,
     util::{block_pos::BlockPos, chunk_pos::ChunkPos},
 };
@@ -11,6 +12,10 @@ use super::physics::{MobPhysicsBundle, MobVelocity};
 
 pub mod camera;
 pub mod interact;
+pub mod inventory;
+
+/// Half the size of the player's collider along each axis.
+pub const PLAYER_HALF_EXTENTS: Vec3 = Vec3::new(0.3, 0.9, 0.3);
 
 pub struct ExcavateManufacturatePlayerPlugin;
 
@@ -42,8 +47,21 @@ impl Plugin for ExcavateManufacturatePlayerPlugin {
                         .chain()
                         .after(super::physics::resolve_mob_velocity),
                     (
+                        inventory::select_hotbar_slot,
+                        inventory::update_held_item,
                         interact::raycast,
-                        (interact::draw_crosshair, interact::handle_destroy_block),
+                        (
+                            interact::draw_crosshair,
+                            interact::handle_destroy_block_creative
+                                .run_if(in_state(state::GameModeState::Creative)),
+                            (
+                                interact::handle_destroy_block_survival,
+                                interact::draw_break_progress,
+                            )
+                                .chain()
+                                .run_if(in_state(state::GameModeState::Survival)),
+                            interact::handle_place_block,
+                        ),
                     )
                         .chain(),
                 )
@@ -51,6 +69,10 @@ impl Plugin for ExcavateManufacturatePlayerPlugin {
                         in_state(state::MenuState::InGame)
                             .and_then(in_state(state::PlayState::Playing)),
                     ),
+            )
+            .add_systems(
+                Update,
+                inventory::draw_hotbar.run_if(in_state(state::MenuState::InGame)),
             );
     }
 }
@@ -61,7 +83,7 @@ pub struct Player;
 #[derive(Component)]
 pub struct PlayerPhysics;
 
-pub fn setup(mut commands: Commands) {
+pub fn setup(mut commands: Commands, item_registry: Res<ItemRegistryResource>) {
     let x = rand::thread_rng().gen_range(-2000..=2000);
     let z = rand::thread_rng().gen_range(-2000..=2000);
 
@@ -79,6 +101,9 @@ pub fn setup(mut commands: Commands) {
         },
         player_block_pos,
         player_chunk_pos,
+        inventory::starting_inventory(&item_registry),
+        interact::SelectedBlock::default(),
+        interact::HeldTool::default(),
     ));
 
     // Player physics entity
@@ -86,7 +111,11 @@ pub fn setup(mut commands: Commands) {
         PlayerPhysics,
         MobPhysicsBundle {
             transform_bundle: TransformBundle::from_transform(player_transform),
-            collider: Collider::cuboid(0.3, 0.9, 0.3),
+            collider: Collider::cuboid(
+                PLAYER_HALF_EXTENTS.x,
+                PLAYER_HALF_EXTENTS.y,
+                PLAYER_HALF_EXTENTS.z,
+            ),
             ..Default::default()
         },
     ));
@@ -0,0 +1,352 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use bevy::{prelude::*, utils::HashMap};
+use image::RgbaImage;
+
+use crate::world::{
+    block::{
+        definition::{self, BlockDefinition, BlockDefinitionData},
+        BlockName,
+    },
+    worldgen::WorldGenFeature,
+};
+
+/// The namespace of the game's own content. Plugins can't register mods under it.
+pub const BUILT_IN_NAMESPACE: &str = "excavatemanufacturate";
+
+/// The directory that holds the asset folders of every namespace.
+pub fn assets_directory() -> PathBuf {
+    let mut assets_directory = bevy::asset::io::file::FileAssetReader::get_base_path();
+    assets_directory.push("assets");
+    assets_directory
+}
+
+/// Content that a plugin adds to the game under its own namespace. Block and texture names are relative to the
+/// namespace, and block textures may refer to textures of this mod, to textures of other mods by their namespaced
+/// name, or to files in `assets/<namespace>/textures/block`.
+pub struct ModDefinition {
+    pub namespace: String,
+    pub blocks: Vec<(String, BlockDefinitionData)>,
+    pub textures: Vec<(String, RgbaImage)>,
+    pub world_gen_features: Vec<Box<dyn WorldGenFeature>>,
+}
+
+impl ModDefinition {
+    pub fn new(namespace: impl Into<String>) -> Self {
+        Self {
+            namespace: namespace.into(),
+            blocks: Vec::new(),
+            textures: Vec::new(),
+            world_gen_features: Vec::new(),
+        }
+    }
+
+    pub fn with_block(mut self, name: impl Into<String>, data: BlockDefinitionData) -> Self {
+        self.blocks.push((name.into(), data));
+        self
+    }
+
+    pub fn with_texture(mut self, name: impl Into<String>, image: RgbaImage) -> Self {
+        self.textures.push((name.into(), image));
+        self
+    }
+
+    pub fn with_world_gen_feature(mut self, feature: impl WorldGenFeature + 'static) -> Self {
+        self.world_gen_features.push(Box::new(feature));
+        self
+    }
+}
+
+/// Mods that plugins registered while the app was being built, waiting to be loaded at startup.
+#[derive(Resource, Default)]
+pub struct PendingMods(Vec<ModDefinition>);
+
+/// Lets plugins register a mod from [`Plugin::build`]. Mods have to be registered before the app starts running,
+/// since the registries are frozen at startup.
+pub trait RegisterModExt {
+    fn register_mod(&mut self, definition: ModDefinition) -> &mut Self;
+}
+
+impl RegisterModExt for App {
+    fn register_mod(&mut self, definition: ModDefinition) -> &mut Self {
+        self.world
+            .get_resource_or_insert_with(PendingMods::default)
+            .0
+            .push(definition);
+        self
+    }
+}
+
+pub struct ModInfo {
+    pub namespace: String,
+
+    /// The mod's folder in the assets directory, if it has one.
+    pub asset_folder: Option<PathBuf>,
+
+    /// Whether a plugin registered content under this namespace.
+    pub plugin: bool,
+
+    pub blocks: Vec<BlockName>,
+    pub world_gen_features: usize,
+}
+
+impl ModInfo {
+    pub fn is_built_in(&self) -> bool {
+        self.namespace == BUILT_IN_NAMESPACE
+    }
+}
+
+/// A problem in or between mods. The invalid or conflicting content is left out, but the game still starts.
+pub enum ModConflict {
+    InvalidNamespace(String),
+    ReservedNamespace(String),
+    DuplicateNamespace(String),
+    DuplicateBlock(BlockName),
+    InvalidBlock {
+        name: String,
+        reason: definition::BlockDefinitionErrorKind,
+    },
+    InvalidBlockFile(definition::BlockDefinitionError),
+    DuplicateTexture(String),
+    InvalidTexture(String),
+}
+
+impl fmt::Display for ModConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModConflict::InvalidNamespace(namespace) => write!(
+                f,
+                "namespace \"{}\" may only contain lowercase letters, digits and underscores",
+                namespace
+            ),
+            ModConflict::ReservedNamespace(namespace) => {
+                write!(f, "namespace {} is reserved for the game", namespace)
+            }
+            ModConflict::DuplicateNamespace(namespace) => write!(
+                f,
+                "namespace {} is registered by more than one plugin, only the first one is loaded",
+                namespace
+            ),
+            ModConflict::DuplicateBlock(name) => write!(
+                f,
+                "block {} is defined more than once, only the first definition is used",
+                name.0
+            ),
+            ModConflict::InvalidBlock { name, reason } => {
+                write!(f, "block {} is invalid: {}", name, reason)
+            }
+            ModConflict::InvalidBlockFile(err) => write!(f, "invalid block definition {}", err),
+            ModConflict::DuplicateTexture(name) => write!(
+                f,
+                "texture {} is provided more than once, only the first one is used",
+                name
+            ),
+            ModConflict::InvalidTexture(name) => write!(
+                f,
+                "texture name \"{}\" may only contain lowercase letters, digits and underscores",
+                name
+            ),
+        }
+    }
+}
+
+/// The loaded mods, in the order they were loaded, and the conflicts between them.
+#[derive(Resource)]
+pub struct Mods {
+    pub loaded: Vec<ModInfo>,
+    pub conflicts: Vec<ModConflict>,
+}
+
+/// Everything the loaded mods add to the game. Only exists during startup: the registries take their part of it,
+/// and it's removed once they're frozen.
+#[derive(Resource, Default)]
+pub struct ModContent {
+    pub blocks: Vec<BlockDefinition>,
+    pub textures: HashMap<String, RgbaImage>,
+    pub world_gen_features: Vec<Box<dyn WorldGenFeature>>,
+}
+
+/// The namespace folders in the assets directory that contain blocks or block textures, sorted by name.
+fn asset_namespaces(assets_directory: &Path) -> Vec<(String, PathBuf)> {
+    let entries = match std::fs::read_dir(assets_directory) {
+        Ok(entries) => entries,
+        Err(err) => {
+            error!(
+                "Couldn't read the assets directory {}: {}",
+                assets_directory.display(),
+                err
+            );
+            return Vec::new();
+        }
+    };
+
+    let mut namespaces: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.join(definition::BLOCKS_DIRECTORY).is_dir()
+                || path
+                    .join(crate::world::block::atlas::BLOCK_TEXTURES_DIRECTORY)
+                    .is_dir()
+        })
+        .filter_map(|path| {
+            let namespace = path.file_name()?.to_str()?.to_owned();
+            Some((namespace, path))
+        })
+        .collect();
+
+    namespaces.sort();
+    namespaces
+}
+
+/// Adds the content of a plugin's mod, reporting whatever conflicts with the mods that were loaded before it.
+fn load_plugin_mod(
+    mod_definition: ModDefinition,
+    mods: &mut Vec<ModInfo>,
+    content: &mut ModContent,
+    conflicts: &mut Vec<ModConflict>,
+) {
+    let namespace = mod_definition.namespace;
+
+    if !definition::is_valid_name_part(&namespace) {
+        conflicts.push(ModConflict::InvalidNamespace(namespace));
+        return;
+    }
+
+    if namespace == BUILT_IN_NAMESPACE {
+        conflicts.push(ModConflict::ReservedNamespace(namespace));
+        return;
+    }
+
+    // A plugin can ship its assets in a folder of the same name, they make up one mod
+    let info = match mods.iter().position(|info| info.namespace == namespace) {
+        Some(index) if mods[index].plugin => {
+            conflicts.push(ModConflict::DuplicateNamespace(namespace));
+            return;
+        }
+        Some(index) => &mut mods[index],
+        None => {
+            mods.push(ModInfo {
+                namespace: namespace.clone(),
+                asset_folder: None,
+                plugin: false,
+                blocks: Vec::new(),
+                world_gen_features: 0,
+            });
+            mods.last_mut().unwrap()
+        }
+    };
+
+    info.plugin = true;
+
+    for (name, data) in mod_definition.blocks {
+        let block = match BlockDefinition::new(&namespace, &name, data) {
+            Ok(block) => block,
+            Err(reason) => {
+                conflicts.push(ModConflict::InvalidBlock {
+                    name: format!("{}/{}", namespace, name),
+                    reason,
+                });
+                continue;
+            }
+        };
+
+        if content
+            .blocks
+            .iter()
+            .any(|existing| existing.name == block.name)
+        {
+            conflicts.push(ModConflict::DuplicateBlock(block.name));
+            continue;
+        }
+
+        info.blocks.push(block.name.clone());
+        content.blocks.push(block);
+    }
+
+    for (name, image) in mod_definition.textures {
+        if !definition::is_valid_name_part(&name) {
+            conflicts.push(ModConflict::InvalidTexture(name));
+            continue;
+        }
+
+        let name = format!("{}/{}", namespace, name);
+
+        if content.textures.contains_key(&name) {
+            conflicts.push(ModConflict::DuplicateTexture(name));
+            continue;
+        }
+
+        content.textures.insert(name, image);
+    }
+
+    info.world_gen_features += mod_definition.world_gen_features.len();
+    content
+        .world_gen_features
+        .extend(mod_definition.world_gen_features);
+}
+
+/// Loads the asset folder mods and the mods that plugins registered. Has to run before any registry is created.
+pub fn setup(mut commands: Commands, pending_mods: Option<ResMut<PendingMods>>) {
+    let assets_directory = assets_directory();
+
+    let mut mods = Vec::new();
+    let mut content = ModContent::default();
+    let mut conflicts = Vec::new();
+
+    for (namespace, namespace_directory) in asset_namespaces(&assets_directory) {
+        let (definitions, definition_errors) =
+            definition::load_namespace_block_definitions(&namespace, &namespace_directory);
+        conflicts.extend(
+            definition_errors
+                .into_iter()
+                .map(ModConflict::InvalidBlockFile),
+        );
+
+        mods.push(ModInfo {
+            namespace,
+            asset_folder: Some(namespace_directory),
+            plugin: false,
+            blocks: definitions
+                .iter()
+                .map(|definition| definition.name.clone())
+                .collect(),
+            world_gen_features: 0,
+        });
+        content.blocks.extend(definitions);
+    }
+
+    if let Some(mut pending_mods) = pending_mods {
+        for mod_definition in std::mem::take(&mut pending_mods.0) {
+            load_plugin_mod(mod_definition, &mut mods, &mut content, &mut conflicts);
+        }
+
+        commands.remove_resource::<PendingMods>();
+    }
+
+    // Block IDs are handed out in this order, so it can't depend on the order plugins were added in
+    content.blocks.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+
+    for conflict in conflicts.iter() {
+        warn!("Mod conflict: {}", conflict);
+    }
+
+    info!(
+        "Loaded {} mods with {} blocks",
+        mods.len(),
+        content.blocks.len()
+    );
+
+    commands.insert_resource(Mods {
+        loaded: mods,
+        conflicts,
+    });
+    commands.insert_resource(content);
+}
+
+/// Removes whatever mod content is left once the registries have taken theirs, nothing can be registered after this.
+pub fn freeze(mut commands: Commands) {
+    commands.remove_resource::<ModContent>();
+}
@@ -30,34 +30,38 @@ impl BlockPos {
                 .any()
     }
 
+    /// The chunks whose meshes depend on this block: its own chunk, and every neighboring chunk (including the ones
+    /// across edges and corners, which ambient occlusion reads from) that the block borders.
     pub fn get_touched_chunk_positions(self) -> Vec<ChunkPos> {
         let chunk_pos = ChunkPos::from(self);
 
-        let mut chunk_positions = Vec::with_capacity(7);
-        chunk_positions.push(chunk_pos);
-
         let on_chunk_borders_neg = self.as_chunk_offset().cmpeq(IVec3::splat(0));
         let on_chunk_borders_pos = self
             .as_chunk_offset()
             .cmpeq(IVec3::splat(CHUNK_SIZE_INT - 1));
 
-        if on_chunk_borders_pos.x {
-            chunk_positions.push(chunk_pos + ChunkPos::new(1, 0, 0));
-        }
-        if on_chunk_borders_neg.x {
-            chunk_positions.push(chunk_pos - ChunkPos::new(1, 0, 0));
-        }
-        if on_chunk_borders_pos.y {
-            chunk_positions.push(chunk_pos + ChunkPos::new(0, 1, 0));
-        }
-        if on_chunk_borders_neg.y {
-            chunk_positions.push(chunk_pos - ChunkPos::new(0, 1, 0));
-        }
-        if on_chunk_borders_pos.z {
-            chunk_positions.push(chunk_pos + ChunkPos::new(0, 0, 1));
-        }
-        if on_chunk_borders_neg.z {
-            chunk_positions.push(chunk_pos - ChunkPos::new(0, 0, 1));
+        // The chunk offsets the block touches along a single axis
+        let axis_offsets = |axis: usize| {
+            let mut offsets = vec![0];
+
+            if on_chunk_borders_pos.test(axis) {
+                offsets.push(1);
+            }
+            if on_chunk_borders_neg.test(axis) {
+                offsets.push(-1);
+            }
+
+            offsets
+        };
+
+        let mut chunk_positions = Vec::with_capacity(8);
+
+        for &x in axis_offsets(0).iter() {
+            for &y in axis_offsets(1).iter() {
+                for &z in axis_offsets(2).iter() {
+                    chunk_positions.push(chunk_pos + ChunkPos::new(x, y, z));
+                }
+            }
         }
 
         chunk_positions
@@ -1,21 +1,85 @@
 use bevy::{
     prelude::*,
-    render::{mesh::Indices, render_asset::RenderAssetUsages, render_resource::PrimitiveTopology},
+    render::{
+        mesh::{Indices, VertexAttributeValues},
+        render_asset::RenderAssetUsages,
+        render_resource::PrimitiveTopology,
+    },
 };
 
-use crate::world::block::static_block_data::AtlasCoordinates;
+use crate::world::{
+    block::static_block_data::{AtlasCoordinates, FaceTexture},
+    light::MAX_LIGHT_LEVEL,
+    render::ChunkMaterial,
+};
+
+/// How chunk geometry is turned into quads.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MeshingMode {
+    /// One quad per visible block face.
+    Naive,
+
+    /// Merges neighboring coplanar faces with the same texture into larger quads.
+    #[default]
+    Greedy,
+}
+
+/// Turns and mirrors the texture on a face by reordering and negating its UVs. Since the texture repeats every whole
+/// UV, negating a coordinate mirrors the texture within each block.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UvTransform {
+    /// Exchanges the u and v coordinates, which is a quarter turn combined with a mirror.
+    pub swap: bool,
+    pub flip_u: bool,
+    pub flip_v: bool,
+}
+
+impl UvTransform {
+    /// Turns the texture clockwise by the given number of quarter turns.
+    pub fn clockwise_rotation(quarter_turns: u16) -> Self {
+        let (swap, flip_u, flip_v) = match quarter_turns % 4 {
+            0 => (false, false, false),
+            1 => (true, false, true),
+            2 => (false, true, true),
+            _ => (true, true, false),
+        };
+
+        Self {
+            swap,
+            flip_u,
+            flip_v,
+        }
+    }
+
+    pub fn apply(self, uv: [f32; 2]) -> [f32; 2] {
+        let [u, v] = if self.swap { [uv[1], uv[0]] } else { uv };
+
+        [
+            if self.flip_u { -u } else { u },
+            if self.flip_v { -v } else { v },
+        ]
+    }
+
+    /// The transform that applies this one and then `next`.
+    pub fn then(self, next: Self) -> Self {
+        // Distinct magnitudes show where each coordinate ends up
+        let [u, v] = next.apply(self.apply([1.0, 2.0]));
 
-pub enum BlockFace {
-    Top,
-    Side,
-    Bottom,
+        Self {
+            swap: u.abs() == 2.0,
+            flip_u: u < 0.0,
+            flip_v: v < 0.0,
+        }
+    }
 }
 
 pub struct ChunkMeshBuilder {
     pub vertices: Vec<[f32; 3]>,
     pub normals: Vec<[f32; 3]>,
     pub uvs: Vec<[f32; 2]>,
+    pub atlas_rects: Vec<[f32; 4]>,
     pub ao: Vec<f32>,
+    pub light: Vec<[f32; 2]>,
     pub indices: Vec<u32>,
 }
 
@@ -25,69 +89,131 @@ impl ChunkMeshBuilder {
             vertices: Vec::new(),
             normals: Vec::new(),
             uvs: Vec::new(),
+            atlas_rects: Vec::new(),
             ao: Vec::new(),
+            light: Vec::new(),
             indices: Vec::new(),
         }
     }
 
-    fn get_face_indices(starting_index: u32) -> [u32; 6] {
-        [
-            starting_index,
-            starting_index + 2,
-            starting_index + 1,
-            starting_index + 2,
-            starting_index + 3,
-            starting_index + 1,
-        ]
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
     }
 
-    fn transform_uvs(
-        uvs: &mut [[f32; 2]; 4],
-        atlas_coords: AtlasCoordinates,
-        atlas_size: (usize, usize),
-    ) {
-        let one_texel = (1.0 / atlas_size.0 as f32, 1.0 / atlas_size.1 as f32);
+    /// The maximum ambient occlusion level, for a vertex that isn't occluded at all.
+    pub const AO_UNOCCLUDED: u8 = 3;
 
-        let starting_x = atlas_coords.min.0 as f32 * one_texel.0;
-        let starting_y = atlas_coords.min.1 as f32 * one_texel.1;
+    /// Splits the face into two triangles. The split is normally along the diagonal between vertices 1 and 2, but
+    /// if vertices 0 and 3 are brighter, the split is flipped so ambient occlusion interpolates symmetrically.
+    fn get_face_indices(starting_index: u32, ao: [u8; 4]) -> [u32; 6] {
+        if ao[0] + ao[3] > ao[1] + ao[2] {
+            [
+                starting_index,
+                starting_index + 2,
+                starting_index + 3,
+                starting_index,
+                starting_index + 3,
+                starting_index + 1,
+            ]
+        } else {
+            [
+                starting_index,
+                starting_index + 2,
+                starting_index + 1,
+                starting_index + 2,
+                starting_index + 3,
+                starting_index + 1,
+            ]
+        }
+    }
 
-        let ending_x = atlas_coords.max.0 as f32 * one_texel.0;
-        let ending_y = atlas_coords.max.1 as f32 * one_texel.1;
+    /// The area of the texture atlas covered by the atlas coordinates, as `[min_u, min_v, max_u, max_v]`. UVs of the
+    /// mesh are relative to this area, with every whole number repeating the texture, so merged faces keep tiling.
+    fn atlas_rect(atlas_coords: AtlasCoordinates, atlas_size: (usize, usize)) -> [f32; 4] {
+        let one_texel = (1.0 / atlas_size.0 as f32, 1.0 / atlas_size.1 as f32);
 
-        for uv in uvs.iter_mut() {
-            uv[0] = uv[0] * (ending_x - starting_x) + starting_x;
-            uv[1] = uv[1] * (ending_y - starting_y) + starting_y;
-        }
+        [
+            atlas_coords.min.0 as f32 * one_texel.0,
+            atlas_coords.min.1 as f32 * one_texel.1,
+            // The max corner is inclusive, so the area ends after its last texel
+            (atlas_coords.max.0 as f32 + 1.0) * one_texel.0,
+            (atlas_coords.max.1 as f32 + 1.0) * one_texel.1,
+        ]
     }
 
     #[allow(clippy::too_many_arguments)]
     pub fn add_face(
+        &mut self,
+        face: [[f32; 3]; 4],
+        normals: [[f32; 3]; 4],
+        uvs: [[f32; 2]; 4],
+        offset: Vec3,
+        vertex_scale: f32,
+        face_texture: FaceTexture,
+        atlas_size: (usize, usize),
+        ao: [u8; 4],
+        light: [u8; 2],
+    ) {
+        self.add_quad(
+            face,
+            normals,
+            uvs,
+            offset,
+            Vec3::splat(vertex_scale),
+            Vec2::ONE,
+            face_texture,
+            atlas_size,
+            ao,
+            light,
+        );
+    }
+
+    /// Adds a face that's scaled along each axis separately. The UVs are scaled by `uv_scale`, which should match the
+    /// size of the quad along the texture axes so the texture repeats once per block, before the texture's UV
+    /// transform is applied. `ao` holds the ambient occlusion level of each vertex, from 0 to
+    /// [`Self::AO_UNOCCLUDED`], and `light` the sky light and block light that shine on the face, from 0 to
+    /// [`MAX_LIGHT_LEVEL`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_quad(
         &mut self,
         mut face: [[f32; 3]; 4],
         normals: [[f32; 3]; 4],
         mut uvs: [[f32; 2]; 4],
         offset: Vec3,
-        vertex_scale: f32,
-        atlas_coords: AtlasCoordinates,
+        size: Vec3,
+        uv_scale: Vec2,
+        face_texture: FaceTexture,
         atlas_size: (usize, usize),
+        ao: [u8; 4],
+        light: [u8; 2],
     ) {
         for vertex in face.iter_mut() {
             for index in 0..3 {
-                vertex[index] *= vertex_scale;
+                vertex[index] *= size[index];
                 vertex[index] += offset[index];
             }
         }
 
-        Self::transform_uvs(&mut uvs, atlas_coords, atlas_size);
+        for uv in uvs.iter_mut() {
+            uv[0] *= uv_scale.x;
+            uv[1] *= uv_scale.y;
+            *uv = face_texture.uv_transform.apply(*uv);
+        }
 
         let starting_index = self.vertices.len();
 
         self.vertices.extend(face);
         self.normals.extend(normals);
         self.uvs.extend(uvs);
+        self.atlas_rects
+            .extend([Self::atlas_rect(face_texture.texture, atlas_size); 4]);
+        self.ao
+            .extend(ao.map(|level| level as f32 / Self::AO_UNOCCLUDED as f32));
+        self.light
+            .extend([light.map(|level| level as f32 / MAX_LIGHT_LEVEL as f32); 4]);
 
         self.indices
-            .extend(Self::get_face_indices(starting_index as u32));
+            .extend(Self::get_face_indices(starting_index as u32, ao));
     }
 
     pub fn into_mesh(self) -> Mesh {
@@ -99,6 +225,9 @@ impl ChunkMeshBuilder {
         mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, self.vertices);
         mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals);
         mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, self.uvs);
+        mesh.insert_attribute(ChunkMaterial::ATTRIBUTE_ATLAS_RECT, self.atlas_rects);
+        mesh.insert_attribute(ChunkMaterial::ATTRIBUTE_AO, self.ao);
+        mesh.insert_attribute(ChunkMaterial::ATTRIBUTE_LIGHT, self.light);
 
         mesh.insert_indices(Indices::U32(self.indices));
 
@@ -106,6 +235,122 @@ impl ChunkMeshBuilder {
     }
 }
 
+/// Reorders the quads of a mesh built by [`ChunkMeshBuilder`] so the ones furthest from `viewer` are drawn first.
+/// Blended faces only cover what was drawn before them, so they have to be drawn back to front.
+pub fn sort_quads_back_to_front(mesh: &mut Mesh, viewer: Vec3) {
+    let (Some(VertexAttributeValues::Float32x3(vertices)), Some(Indices::U32(indices))) =
+        (mesh.attribute(Mesh::ATTRIBUTE_POSITION), mesh.indices())
+    else {
+        return;
+    };
+
+    // Every quad has four vertices of its own and six indices
+    let mut quads: Vec<(f32, &[u32])> = indices
+        .chunks_exact(6)
+        .map(|quad_indices| {
+            let first_vertex = *quad_indices.iter().min().unwrap() as usize;
+            let center = vertices[first_vertex..first_vertex + 4]
+                .iter()
+                .map(|&vertex| Vec3::from(vertex))
+                .sum::<Vec3>()
+                / 4.0;
+
+            (center.distance_squared(viewer), quad_indices)
+        })
+        .collect();
+
+    quads.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+
+    let sorted_indices = quads
+        .into_iter()
+        .flat_map(|(_, quad_indices)| quad_indices.iter().copied())
+        .collect();
+
+    mesh.insert_indices(Indices::U32(sorted_indices));
+}
+
+/// The triangles of a chunk's collider. Blocks don't always collide with the shape they're drawn with, plants for
+/// example can be walked through, so the collider is built next to the chunk mesh instead of from it.
+#[derive(Default)]
+pub struct CollisionMeshBuilder {
+    pub vertices: Vec<Vec3>,
+    pub indices: Vec<[u32; 3]>,
+}
+
+impl CollisionMeshBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Adds a face from the [`faces`] tables, scaled along each axis by `size` and moved by `offset`.
+    pub fn add_quad(&mut self, face: [[f32; 3]; 4], offset: Vec3, size: Vec3) {
+        let starting_index = self.vertices.len() as u32;
+
+        self.vertices
+            .extend(face.map(|vertex| Vec3::from(vertex) * size + offset));
+
+        self.indices.extend([
+            [starting_index, starting_index + 2, starting_index + 1],
+            [starting_index + 2, starting_index + 3, starting_index + 1],
+        ]);
+    }
+
+    /// Adds all six faces of a box.
+    pub fn add_box(&mut self, min: Vec3, max: Vec3) {
+        for (_, geometry, ..) in NEIGHBOR_DATA {
+            self.add_quad(geometry, min, max - min);
+        }
+    }
+}
+
+/// The axis (0 for x, 1 for y, 2 for z) that a face direction points along.
+pub fn normal_axis(direction: IVec3) -> usize {
+    if direction.x != 0 {
+        0
+    } else if direction.y != 0 {
+        1
+    } else {
+        2
+    }
+}
+
+/// The index of the [`NEIGHBOR_DATA`] entry for a face direction, which is one of the six unit directions.
+pub fn face_index(direction: IVec3) -> usize {
+    NEIGHBOR_DATA
+        .iter()
+        .position(|&((dx, dy, dz), ..)| IVec3::new(dx, dy, dz) == direction)
+        .expect("Face directions are unit vectors along an axis")
+}
+
+/// The axes along which the u and v texture coordinates of a face increase, for a face whose normal points along
+/// the given axis. Matches the orientation of the [`uvs`] tables.
+pub const fn uv_axes(normal_axis: usize) -> (usize, usize) {
+    match normal_axis {
+        0 => (2, 1),
+        1 => (0, 2),
+        _ => (0, 1),
+    }
+}
+
+/// The directions in which the u and v texture coordinates of a face increase, taken from its geometry and UV tables.
+pub fn uv_directions(geometry: [[f32; 3]; 4], uvs: [[f32; 2]; 4]) -> (IVec3, IVec3) {
+    let vertex_at = |uv: [f32; 2]| {
+        let index = uvs.iter().position(|&vertex_uv| vertex_uv == uv).unwrap();
+        Vec3::from(geometry[index]).as_ivec3()
+    };
+
+    let origin = vertex_at([0.0, 0.0]);
+
+    (
+        vertex_at([1.0, 0.0]) - origin,
+        vertex_at([0.0, 1.0]) - origin,
+    )
+}
+
 pub mod faces {
     pub const FACE_Z_FRONT: [[f32; 3]; 4] = [
         [0.0, 0.0, 1.0], // Bottom left
@@ -199,7 +444,6 @@ pub const NEIGHBOR_DATA: [(
     [[f32; 3]; 4],   // Geometry
     [[f32; 3]; 4],   // Normals
     [[f32; 2]; 4],   // UV
-    BlockFace,       // block face enum used for texture coords
 ); 6] = [
     // Positive z
     (
@@ -207,7 +451,6 @@ pub const NEIGHBOR_DATA: [(
         faces::FACE_Z_FRONT,
         normals::NORMAL_Z_FRONT,
         uvs::UV_Z_FRONT,
-        BlockFace::Side,
     ),
     // Negative z
     (
@@ -215,7 +458,6 @@ pub const NEIGHBOR_DATA: [(
         faces::FACE_Z_BACK,
         normals::NORMAL_Z_BACK,
         uvs::UV_Z_BACK,
-        BlockFace::Side,
     ),
     // Positive y
     (
@@ -223,7 +465,6 @@ pub const NEIGHBOR_DATA: [(
         faces::FACE_Y_FRONT,
         normals::NORMAL_Y_FRONT,
         uvs::UV_Y_FRONT,
-        BlockFace::Top,
     ),
     // Negative y
     (
@@ -231,7 +472,6 @@ pub const NEIGHBOR_DATA: [(
         faces::FACE_Y_BACK,
         normals::NORMAL_Y_BACK,
         uvs::UV_Y_BACK,
-        BlockFace::Bottom,
     ),
     // Positive x
     (
@@ -239,7 +479,6 @@ pub const NEIGHBOR_DATA: [(
         faces::FACE_X_FRONT,
         normals::NORMAL_X_FRONT,
         uvs::UV_X_FRONT,
-        BlockFace::Side,
     ),
     // Negative x
     (
@@ -247,6 +486,5 @@ pub const NEIGHBOR_DATA: [(
         faces::FACE_X_BACK,
         normals::NORMAL_X_BACK,
         uvs::UV_X_BACK,
-        BlockFace::Side,
     ),
 ];
@@ -5,8 +5,53 @@ use bevy::math::{IVec3, Vec3};
 pub struct Hit {
     pub position: Vec3,
     pub normal: Vec3,
+
+    /// The voxel that was hit, in the same coordinates as [`super::block_pos::BlockPos`].
+    pub voxel: IVec3,
+}
+
+/// Where a ray first enters a box, as the distance along the ray in multiples of its direction and the normal of
+/// the side it enters through. Returns `None` if the ray misses the box or starts inside of it.
+pub fn intersect_box(ray_pos: Vec3, ray_dir: Vec3, min: Vec3, max: Vec3) -> Option<(f32, Vec3)> {
+    let mut entry = f32::NEG_INFINITY;
+    let mut exit = f32::INFINITY;
+    let mut entry_axis = 0;
+
+    for axis in 0..3 {
+        if ray_dir[axis] == 0.0 {
+            // The ray runs parallel to both sides on this axis, so it has to start between them
+            if ray_pos[axis] < min[axis] || ray_pos[axis] > max[axis] {
+                return None;
+            }
+
+            continue;
+        }
+
+        let to_min = (min[axis] - ray_pos[axis]) / ray_dir[axis];
+        let to_max = (max[axis] - ray_pos[axis]) / ray_dir[axis];
+
+        // The ray enters through the side it crosses last
+        if to_min.min(to_max) > entry {
+            entry = to_min.min(to_max);
+            entry_axis = axis;
+        }
+
+        exit = exit.min(to_min.max(to_max));
+    }
+
+    // Also rejects rays without a direction, which never enter anything
+    if entry < 0.0 || entry > exit {
+        return None;
+    }
+
+    let mut normal = Vec3::ZERO;
+    normal[entry_axis] = -ray_dir[entry_axis].signum();
+
+    Some((entry, normal))
 }
 
+/// Walks along the ray voxel by voxel for up to `steps` voxels. The hit evaluator gets where the ray enters each
+/// voxel, and returns where the ray actually hits something in that voxel, or `None` to keep going.
 pub fn raytrace_dda<F>(
     ray_pos: Vec3,
     ray_dir: Vec3,
@@ -14,11 +59,12 @@ pub fn raytrace_dda<F>(
     mut hit_evaluator: F,
 ) -> Option<Hit>
 where
-    F: FnMut(IVec3) -> bool,
+    F: FnMut(&Hit) -> Option<Hit>,
 {
     let mut hit = Hit {
         position: Vec3::ZERO,
         normal: Vec3::ZERO,
+        voxel: IVec3::ZERO,
     };
 
     let step_sizes = 1.0 / ray_dir.abs();
@@ -56,12 +102,11 @@ where
         next_dist -= closest_dist;
         next_dist += step_sizes * step_axis;
 
-        hit.normal = step_axis;
-
-        if hit_evaluator(voxel_pos.floor().as_ivec3() + 1) {
-            hit.position = current_pos;
-            hit.normal *= -step_dir;
+        hit.position = current_pos;
+        hit.normal = step_axis * -step_dir;
+        hit.voxel = voxel_pos.floor().as_ivec3() + 1;
 
+        if let Some(hit) = hit_evaluator(&hit) {
             return Some(hit);
         }
     }
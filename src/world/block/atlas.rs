@@ -0,0 +1,174 @@
+use std::path::{Path, PathBuf};
+
+use bevy::{prelude::*, utils::HashMap};
+use image::RgbaImage;
+
+use super::static_block_data::AtlasCoordinates;
+
+/// The directory inside of a namespace's asset folder that holds its block textures.
+pub const BLOCK_TEXTURES_DIRECTORY: &str = "textures/block";
+
+/// The size of the placeholder texture if no block texture could be loaded to take the size from.
+const DEFAULT_TEXTURE_SIZE: u32 = 16;
+
+/// A texture atlas stitched together from individual block textures, which are all the same size.
+pub struct StitchedAtlas {
+    pub image: RgbaImage,
+    coordinates: HashMap<String, AtlasCoordinates>,
+
+    /// Where the placeholder texture is, used for textures that are missing or couldn't be loaded.
+    placeholder: AtlasCoordinates,
+}
+
+impl StitchedAtlas {
+    pub fn size(&self) -> (usize, usize) {
+        (self.image.width() as usize, self.image.height() as usize)
+    }
+
+    /// Gets the location of a texture by its namespaced name, such as `excavatemanufacturate/stone`. Textures that
+    /// weren't stitched into the atlas use the placeholder texture.
+    pub fn get(&self, texture_name: &str) -> AtlasCoordinates {
+        self.coordinates
+            .get(texture_name)
+            .copied()
+            .unwrap_or(self.placeholder)
+    }
+}
+
+/// The file of a namespaced texture name, `<namespace>/<name>` maps to
+/// `assets/<namespace>/textures/block/<name>.png`.
+fn texture_path(assets_directory: &Path, texture_name: &str) -> Option<PathBuf> {
+    let (namespace, name) = texture_name.split_once('/')?;
+
+    let mut path = assets_directory.join(namespace);
+    path.push(BLOCK_TEXTURES_DIRECTORY);
+    path.push(format!("{}.png", name));
+
+    Some(path)
+}
+
+/// A magenta and black checkerboard, so missing textures stand out.
+fn placeholder_texture(size: u32) -> RgbaImage {
+    let half = (size / 2).max(1);
+
+    RgbaImage::from_fn(size, size, |x, y| {
+        if (x / half + y / half) % 2 == 1 {
+            image::Rgba([0, 0, 0, 255])
+        } else {
+            image::Rgba([255, 0, 255, 255])
+        }
+    })
+}
+
+fn atlas_coordinates(tile: usize, columns: usize, texture_size: u32) -> AtlasCoordinates {
+    let x = (tile % columns) as u32 * texture_size;
+    let y = (tile / columns) as u32 * texture_size;
+
+    AtlasCoordinates {
+        min: (x as u16, y as u16),
+        max: ((x + texture_size - 1) as u16, (y + texture_size - 1) as u16),
+    }
+}
+
+/// Loads every named texture and stitches them into a square-ish grid. Textures that mods provided directly take
+/// precedence over files in the asset folders. Textures that are missing, can't be read or don't match the size of
+/// the other textures are reported and replaced by a placeholder.
+pub fn stitch_atlas<'a>(
+    assets_directory: &Path,
+    texture_names: impl IntoIterator<Item = &'a str>,
+    mut provided_textures: HashMap<String, RgbaImage>,
+) -> StitchedAtlas {
+    let mut texture_names: Vec<&str> = texture_names.into_iter().collect();
+    texture_names.sort_unstable();
+    texture_names.dedup();
+
+    let mut textures: Vec<(&str, RgbaImage)> = Vec::with_capacity(texture_names.len());
+
+    for texture_name in texture_names {
+        if let Some(image) = provided_textures.remove(texture_name) {
+            textures.push((texture_name, image));
+            continue;
+        }
+
+        let Some(path) = texture_path(assets_directory, texture_name) else {
+            error!(
+                "Texture name {} is missing a namespace, using the placeholder texture",
+                texture_name
+            );
+            continue;
+        };
+
+        match image::open(&path) {
+            Ok(image) => textures.push((texture_name, image.into_rgba8())),
+            Err(err) => warn!(
+                "Couldn't load texture {} from {}, using the placeholder texture: {}",
+                texture_name,
+                path.display(),
+                err
+            ),
+        }
+    }
+
+    // Every texture has to match the size of the first one
+    let texture_size = textures
+        .first()
+        .map_or(DEFAULT_TEXTURE_SIZE, |(_, image)| image.width());
+
+    textures.retain(|(texture_name, image)| {
+        let matches = image.width() == texture_size && image.height() == texture_size;
+
+        if !matches {
+            error!(
+                "Texture {} is {}x{}, but block textures are {}x{}, using the placeholder texture",
+                texture_name,
+                image.width(),
+                image.height(),
+                texture_size,
+                texture_size
+            );
+        }
+
+        matches
+    });
+
+    // The placeholder takes up the first tile
+    let tile_count = textures.len() + 1;
+    let columns = (tile_count as f32).sqrt().ceil() as usize;
+    let rows = tile_count.div_ceil(columns);
+
+    let mut image = RgbaImage::new(columns as u32 * texture_size, rows as u32 * texture_size);
+    let mut coordinates = HashMap::new();
+
+    let placeholder = atlas_coordinates(0, columns, texture_size);
+    image::imageops::replace(
+        &mut image,
+        &placeholder_texture(texture_size),
+        placeholder.min.0 as i64,
+        placeholder.min.1 as i64,
+    );
+
+    for (tile, (texture_name, texture)) in textures.into_iter().enumerate() {
+        let coords = atlas_coordinates(tile + 1, columns, texture_size);
+
+        image::imageops::replace(
+            &mut image,
+            &texture,
+            coords.min.0 as i64,
+            coords.min.1 as i64,
+        );
+        coordinates.insert(texture_name.to_owned(), coords);
+    }
+
+    info!(
+        "Stitched {} block textures into a {}x{} texture atlas",
+        coordinates.len(),
+        image.width(),
+        image.height()
+    );
+
+    StitchedAtlas {
+        image,
+        coordinates,
+        placeholder,
+    }
+}
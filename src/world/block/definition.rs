@@ -0,0 +1,421 @@
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use super::{
+    model::{BlockModel, BlockModelDefinition},
+    static_block_data::{
+        AtlasCoordinates, BlockHardnessLevel, BlockRotation, BlockTextures, RenderLayer,
+        StaticBlockData,
+    },
+    BlockName,
+};
+
+/// The directory inside of a namespace's asset folder that holds its block definitions.
+pub const BLOCKS_DIRECTORY: &str = "blocks";
+
+/// A block definition read from `assets/<namespace>/blocks/<name>.ron` or `<name>.json`, or registered by a mod
+/// plugin. The block's name is taken from the path, so the definition in
+/// `assets/excavatemanufacturate/blocks/stone.ron` is named `excavatemanufacturate/stone`.
+pub struct BlockDefinition {
+    pub name: BlockName,
+    pub data: BlockDefinitionData,
+}
+
+impl BlockDefinition {
+    /// Checks the definition of the block `name` in `namespace` and qualifies its texture names.
+    pub fn new(
+        namespace: &str,
+        name: &str,
+        data: BlockDefinitionData,
+    ) -> Result<Self, BlockDefinitionErrorKind> {
+        if !is_valid_name_part(namespace) || !is_valid_name_part(name) {
+            return Err(BlockDefinitionErrorKind::InvalidName);
+        }
+
+        data.validate().map_err(BlockDefinitionErrorKind::Invalid)?;
+
+        Ok(Self {
+            name: BlockName(Cow::Owned(format!("{}/{}", namespace, name))),
+            data: data.with_qualified_texture_names(namespace),
+        })
+    }
+}
+
+/// The contents of a block definition file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BlockDefinitionData {
+    /// Texture names, either namespaced like `excavatemanufacturate/stone` or relative to the block's own
+    /// namespace like `stone`. Textures are read from `assets/<namespace>/textures/block/<name>.png`.
+    pub textures: BlockTextures<String>,
+    pub hardness: BlockHardnessLevel,
+
+    #[serde(default)]
+    pub render_layer: RenderLayer,
+
+    #[serde(default)]
+    pub transparent: bool,
+
+    #[serde(default)]
+    pub light_emission: u8,
+
+    /// The shape of the block, a full cube if not set.
+    #[serde(default)]
+    pub model: BlockModelDefinition,
+
+    /// The properties that make up the block's states. Every combination of values is a separate state, and the
+    /// first value of every property makes up the default state.
+    ///
+    /// Placing a block sets a property named `axis` with the values `x`, `y` and `z` to the axis of the face it's
+    /// placed against, and a property named `facing` with the values `north`, `south`, `east` and `west` to the
+    /// direction of the player.
+    #[serde(default)]
+    pub properties: Vec<BlockProperty>,
+
+    /// Changes to the textures, rotation and model of the states that match a variant. Later variants win over earlier
+    /// ones.
+    #[serde(default)]
+    pub variants: Vec<BlockVariant>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BlockProperty {
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BlockVariant {
+    /// The property values a state needs to have for this variant to apply to it.
+    pub when: BTreeMap<String, String>,
+
+    #[serde(default)]
+    pub textures: Option<BlockTextures<String>>,
+
+    #[serde(default)]
+    pub rotation: Option<BlockRotation>,
+
+    #[serde(default)]
+    pub model: Option<BlockModelDefinition>,
+}
+
+impl BlockDefinitionData {
+    /// Checks values that parse fine but don't make sense, returning a description of the first problem.
+    fn validate(&self) -> Result<(), String> {
+        if let Some(texture) = self.texture_names().find(|texture| {
+            !texture.split('/').all(is_valid_name_part) || texture.split('/').count() > 2
+        }) {
+            return Err(format!("invalid texture name \"{}\"", texture));
+        }
+
+        if self.light_emission > StaticBlockData::MAX_LIGHT_EMISSION {
+            return Err(format!(
+                "light emission {} is above the maximum of {}",
+                self.light_emission,
+                StaticBlockData::MAX_LIGHT_EMISSION
+            ));
+        }
+
+        self.model.validate()?;
+
+        for (index, property) in self.properties.iter().enumerate() {
+            if !is_valid_name_part(&property.name) {
+                return Err(format!("invalid property name \"{}\"", property.name));
+            }
+
+            if self.properties[..index]
+                .iter()
+                .any(|other| other.name == property.name)
+            {
+                return Err(format!("property {} is declared twice", property.name));
+            }
+
+            if property.values.is_empty() {
+                return Err(format!("property {} has no values", property.name));
+            }
+
+            for (index, value) in property.values.iter().enumerate() {
+                if !is_valid_name_part(value) || property.values[..index].contains(value) {
+                    return Err(format!(
+                        "invalid or repeated value \"{}\" of property {}",
+                        value, property.name
+                    ));
+                }
+            }
+        }
+
+        for variant in self.variants.iter() {
+            for (property, value) in variant.when.iter() {
+                let is_known = self
+                    .properties
+                    .iter()
+                    .any(|declared| declared.name == *property && declared.values.contains(value));
+
+                if !is_known {
+                    return Err(format!(
+                        "variant refers to {}={}, which isn't a declared property value",
+                        property, value
+                    ));
+                }
+            }
+
+            if let Some(rotation) = variant.rotation.filter(|rotation| !rotation.is_valid()) {
+                return Err(format!(
+                    "rotation {:?} isn't made of quarter turns",
+                    rotation
+                ));
+            }
+
+            if let Some(model) = variant.model.as_ref() {
+                model.validate()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Every texture the block uses in any of its states.
+    pub fn texture_names(&self) -> impl Iterator<Item = &String> {
+        self.textures.iter().chain(
+            self.variants
+                .iter()
+                .filter_map(|variant| variant.textures.as_ref())
+                .flat_map(BlockTextures::iter),
+        )
+    }
+
+    /// Makes texture names that are relative to the namespace absolute.
+    fn with_qualified_texture_names(self, namespace: &str) -> Self {
+        let qualify = |texture: String| {
+            if texture.contains('/') {
+                texture
+            } else {
+                format!("{}/{}", namespace, texture)
+            }
+        };
+
+        Self {
+            textures: self.textures.map(qualify),
+            variants: self
+                .variants
+                .into_iter()
+                .map(|variant| BlockVariant {
+                    textures: variant.textures.map(|textures| textures.map(qualify)),
+                    ..variant
+                })
+                .collect(),
+            ..self
+        }
+    }
+
+    /// The property values of every state of the block, starting with the default state.
+    pub fn states(&self) -> Vec<Vec<(String, String)>> {
+        let mut states = vec![Vec::new()];
+
+        for property in self.properties.iter() {
+            states = states
+                .into_iter()
+                .flat_map(|state| {
+                    property.values.iter().map(move |value| {
+                        let mut state = state.clone();
+                        state.push((property.name.clone(), value.clone()));
+                        state
+                    })
+                })
+                .collect();
+        }
+
+        states
+    }
+
+    /// Creates the static data of one of the block's states, looking up where its textures ended up in the atlas.
+    pub fn static_block_data(
+        &self,
+        properties: &[(String, String)],
+        mut atlas_coordinates: impl FnMut(&str) -> AtlasCoordinates,
+    ) -> StaticBlockData {
+        let mut textures = &self.textures;
+        let mut rotation = BlockRotation::default();
+        let mut model = &self.model;
+
+        let matching_variants = self.variants.iter().filter(|variant| {
+            variant.when.iter().all(|(property, value)| {
+                properties
+                    .iter()
+                    .any(|(name, state_value)| name == property && state_value == value)
+            })
+        });
+
+        for variant in matching_variants {
+            textures = variant.textures.as_ref().unwrap_or(textures);
+            rotation = variant.rotation.unwrap_or(rotation);
+            model = variant.model.as_ref().unwrap_or(model);
+        }
+
+        let model = BlockModel::new(model, rotation);
+
+        StaticBlockData {
+            full_faces: model.full_faces(),
+            model,
+            faces: textures
+                .as_ref()
+                .map(|texture| atlas_coordinates(texture))
+                .oriented_faces(rotation),
+            hardness: self.hardness,
+            render_layer: self.render_layer,
+            transparent: self.transparent,
+            light_emission: self.light_emission,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum BlockDefinitionErrorKind {
+    Io(io::Error),
+    Ron(ron::error::SpannedError),
+    Json(serde_json::Error),
+    UnsupportedExtension,
+    InvalidName,
+    Invalid(String),
+}
+
+/// An error in a single block definition file.
+#[derive(Debug)]
+pub struct BlockDefinitionError {
+    pub path: PathBuf,
+    pub kind: BlockDefinitionErrorKind,
+}
+
+impl fmt::Display for BlockDefinitionErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockDefinitionErrorKind::Io(err) => write!(f, "{}", err),
+            BlockDefinitionErrorKind::Ron(err) => write!(f, "invalid RON at {}", err),
+            BlockDefinitionErrorKind::Json(err) => write!(f, "invalid JSON: {}", err),
+            BlockDefinitionErrorKind::UnsupportedExtension => {
+                write!(f, "block definitions must be .ron or .json files")
+            }
+            BlockDefinitionErrorKind::InvalidName => write!(
+                f,
+                "block names may only contain lowercase letters, digits and underscores"
+            ),
+            BlockDefinitionErrorKind::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl fmt::Display for BlockDefinitionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.kind)
+    }
+}
+
+/// Whether a namespace, block name or texture name only uses the characters that are allowed in names.
+pub fn is_valid_name_part(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// Reads a single block definition. `namespace` is the name of the asset folder the file is in.
+pub fn load_block_definition(
+    namespace: &str,
+    path: &Path,
+) -> Result<BlockDefinition, BlockDefinitionError> {
+    let error = |kind| BlockDefinitionError {
+        path: path.to_owned(),
+        kind,
+    };
+
+    let name = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or_else(|| error(BlockDefinitionErrorKind::InvalidName))?;
+
+    let contents =
+        std::fs::read_to_string(path).map_err(|err| error(BlockDefinitionErrorKind::Io(err)))?;
+
+    let data: BlockDefinitionData = match path.extension().and_then(|extension| extension.to_str())
+    {
+        Some("ron") => {
+            ron::from_str(&contents).map_err(|err| error(BlockDefinitionErrorKind::Ron(err)))?
+        }
+        Some("json") => serde_json::from_str(&contents)
+            .map_err(|err| error(BlockDefinitionErrorKind::Json(err)))?,
+        _ => return Err(error(BlockDefinitionErrorKind::UnsupportedExtension)),
+    };
+
+    BlockDefinition::new(namespace, name, data).map_err(error)
+}
+
+/// Reads the block definitions in the `blocks` directory of a namespace's asset folder. Definitions are sorted by
+/// name, so the order doesn't depend on the file system. Every broken file is reported, not just the first one.
+pub fn load_namespace_block_definitions(
+    namespace: &str,
+    namespace_directory: &Path,
+) -> (Vec<BlockDefinition>, Vec<BlockDefinitionError>) {
+    let io_error = |path: &Path, err| BlockDefinitionError {
+        path: path.to_owned(),
+        kind: BlockDefinitionErrorKind::Io(err),
+    };
+
+    let mut definitions = Vec::new();
+    let mut errors = Vec::new();
+
+    let blocks_directory = namespace_directory.join(BLOCKS_DIRECTORY);
+    if !blocks_directory.is_dir() {
+        // This namespace doesn't define any blocks
+        return (definitions, errors);
+    }
+
+    let files = match std::fs::read_dir(&blocks_directory) {
+        Ok(files) => files,
+        Err(err) => return (definitions, vec![io_error(&blocks_directory, err)]),
+    };
+
+    let mut definition_files = Vec::new();
+
+    for file in files {
+        match file {
+            Ok(file) => definition_files.push(file.path()),
+            Err(err) => errors.push(io_error(&blocks_directory, err)),
+        }
+    }
+
+    definition_files.sort();
+
+    for path in definition_files {
+        match load_block_definition(namespace, &path) {
+            Ok(definition) => {
+                if definitions
+                    .iter()
+                    .any(|existing: &BlockDefinition| existing.name == definition.name)
+                {
+                    // Both a .ron and a .json file for the same block
+                    errors.push(BlockDefinitionError {
+                        path,
+                        kind: BlockDefinitionErrorKind::Invalid(format!(
+                            "block {} is defined more than once",
+                            definition.name.0
+                        )),
+                    });
+                    continue;
+                }
+
+                definitions.push(definition);
+            }
+            Err(err) => errors.push(err),
+        }
+    }
+
+    (definitions, errors)
+}
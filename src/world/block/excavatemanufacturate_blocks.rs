@@ -1,53 +1,50 @@
+/// Names of the built-in blocks that the game refers to directly. Their definitions are loaded from
+/// `assets/excavatemanufacturate/blocks`.
 pub mod block_names {
+    use std::borrow::Cow;
+
     use crate::world::block::BlockName;
 
-    pub const GRASS: BlockName = BlockName("excavatemanufacturate/grass");
-    pub const DIRT: BlockName = BlockName("excavatemanufacturate/dirt");
-    pub const BEDROCK: BlockName = BlockName("excavatemanufacturate/bedrock");
-    pub const STONE: BlockName = BlockName("excavatemanufacturate/stone");
-}
+    pub const GRASS: BlockName = BlockName(Cow::Borrowed("excavatemanufacturate/grass"));
+    pub const DIRT: BlockName = BlockName(Cow::Borrowed("excavatemanufacturate/dirt"));
+    pub const BEDROCK: BlockName = BlockName(Cow::Borrowed("excavatemanufacturate/bedrock"));
+    pub const STONE: BlockName = BlockName(Cow::Borrowed("excavatemanufacturate/stone"));
+    pub const LOG: BlockName = BlockName(Cow::Borrowed("excavatemanufacturate/log"));
+    pub const LEAVES: BlockName = BlockName(Cow::Borrowed("excavatemanufacturate/leaves"));
+    pub const FURNACE: BlockName = BlockName(Cow::Borrowed("excavatemanufacturate/furnace"));
+    pub const STONE_SLAB: BlockName = BlockName(Cow::Borrowed("excavatemanufacturate/stone_slab"));
+    pub const STONE_STAIRS: BlockName =
+        BlockName(Cow::Borrowed("excavatemanufacturate/stone_stairs"));
+    pub const GLASS: BlockName = BlockName(Cow::Borrowed("excavatemanufacturate/glass"));
+    pub const TALL_GRASS: BlockName = BlockName(Cow::Borrowed("excavatemanufacturate/tall_grass"));
+    pub const LAMP: BlockName = BlockName(Cow::Borrowed("excavatemanufacturate/lamp"));
+    pub const SAND: BlockName = BlockName(Cow::Borrowed("excavatemanufacturate/sand"));
+    pub const SNOW: BlockName = BlockName(Cow::Borrowed("excavatemanufacturate/snow"));
+    pub const COAL_ORE: BlockName = BlockName(Cow::Borrowed("excavatemanufacturate/coal_ore"));
+    pub const COPPER_ORE: BlockName = BlockName(Cow::Borrowed("excavatemanufacturate/copper_ore"));
+    pub const IRON_ORE: BlockName = BlockName(Cow::Borrowed("excavatemanufacturate/iron_ore"));
 
-pub mod block_data {
-    use crate::world::block::static_block_data::{
-        AtlasCoordinates, BlockHardnessLevel, BlockTextures, StaticBlockData, ToolType,
-    };
+    /// Stands in for blocks in a world save that no longer exist, for example because their mod was removed.
+    pub const MISSING: BlockName = BlockName(Cow::Borrowed("excavatemanufacturate/missing"));
 
-    pub const GRASS: StaticBlockData = StaticBlockData {
-        textures: BlockTextures {
-            top: AtlasCoordinates {
-                min: (0, 0),
-                max: (15, 15),
-            },
-            sides: Some(AtlasCoordinates {
-                min: (0, 16),
-                max: (15, 31),
-            }),
-            bottom: Some(AtlasCoordinates {
-                min: (0, 32),
-                max: (15, 47),
-            }),
-        },
-        hardness: BlockHardnessLevel::Hand,
-    };
-    pub const DIRT: StaticBlockData = StaticBlockData {
-        textures: BlockTextures::from_single(AtlasCoordinates {
-            min: (0, 32),
-            max: (15, 47),
-        }),
-        hardness: BlockHardnessLevel::Hand,
-    };
-    pub const BEDROCK: StaticBlockData = StaticBlockData {
-        textures: BlockTextures::from_single(AtlasCoordinates {
-            min: (16, 16),
-            max: (31, 31),
-        }),
-        hardness: BlockHardnessLevel::Unbreakable,
-    };
-    pub const STONE: StaticBlockData = StaticBlockData {
-        textures: BlockTextures::from_single(AtlasCoordinates {
-            min: (16, 0),
-            max: (31, 15),
-        }),
-        hardness: BlockHardnessLevel::Tool(ToolType::Pickaxe, 0),
-    };
+    pub const ALL: [BlockName; 18] = [
+        GRASS,
+        DIRT,
+        BEDROCK,
+        STONE,
+        LOG,
+        LEAVES,
+        FURNACE,
+        STONE_SLAB,
+        STONE_STAIRS,
+        GLASS,
+        TALL_GRASS,
+        LAMP,
+        SAND,
+        SNOW,
+        COAL_ORE,
+        COPPER_ORE,
+        IRON_ORE,
+        MISSING,
+    ];
 }
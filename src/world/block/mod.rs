@@ -1,4 +1,4 @@
-use std::{hash::Hash, num::NonZeroU16};
+use std::{borrow::Cow, hash::Hash, num::NonZeroU16};
 
 use bevy::{ecs::system::EntityCommands, prelude::*};
 
@@ -6,13 +6,17 @@ use crate::util::block_pos::BlockPos;
 
 use self::registry::BlockRegistry;
 
+pub mod atlas;
+pub mod definition;
 pub mod dynamic_block_data;
 pub mod excavatemanufacturate_blocks;
+pub mod model;
 pub mod registry;
+pub mod state;
 pub mod static_block_data;
 
 /// The data contained in the world. A newtype of `Option<BlockData>`, contains either a block or no block.
-#[derive(Debug, Clone, Deref, DerefMut)]
+#[derive(Debug, Clone, PartialEq, Eq, Deref, DerefMut)]
 pub struct BlockData(Option<Block>);
 
 impl BlockData {
@@ -27,17 +31,28 @@ impl BlockData {
     }
 }
 
-#[derive(Debug, Clone, Copy, Deref, DerefMut, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct BlockName(pub &'static str);
+/// A namespaced block name like `excavatemanufacturate/stone`. Built-in names are borrowed from constants, names of
+/// blocks that mods define at runtime are owned.
+#[derive(Debug, Clone, Deref, DerefMut, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BlockName(pub Cow<'static, str>);
 
 /// Represents an identifier of static block data. To represent static data, individual blocks need to store this ID instead of the data itself.
 ///
+/// Every state of a block has its own ID, see [`state::BlockState`].
+///
 /// Should never be constructed manually; this value is managed by the block registry.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct BlockId(pub(in crate::world::block) NonZeroU16);
 
+impl BlockId {
+    /// The numeric value of this ID, used when storing blocks on disk.
+    pub fn raw(self) -> u16 {
+        self.0.get()
+    }
+}
+
 /// A lightweight data representation of a single block. Construction of this type is usually handled by the [`BlockRegistry`].
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Block {
     /// A static ID that serves as a pointer to static block data applicable to all blocks of this type.
     pub id: BlockId,
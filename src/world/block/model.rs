@@ -0,0 +1,306 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::util::mesh;
+
+use super::static_block_data::BlockRotation;
+
+/// How many model units make up the edge of a block. Definitions write cuboid corners and UVs in model units, which
+/// line up with the pixels of a 16x16 texture.
+pub const MODEL_UNITS: f32 = 16.0;
+
+/// The names that cuboid definitions use for the faces of a cuboid, in the order of [`mesh::NEIGHBOR_DATA`].
+const FACE_NAMES: [&str; 6] = ["south", "north", "top", "bottom", "east", "west"];
+
+/// The shape of a block as it's written in its definition, like `Cuboids([(from: (0, 0, 0), to: (16, 8, 16))])`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub enum BlockModelDefinition {
+    /// A full cube, which is what most blocks are.
+    #[default]
+    Cube,
+
+    /// Boxes inside of the block, like the lower half of a slab. Every face of a cuboid shows the texture of the
+    /// block face that points the same way.
+    Cuboids(Vec<CuboidDefinition>),
+
+    /// Two quads that cross diagonally through the block and can be seen from both sides, used for plants. Shows the
+    /// north texture of the block.
+    Cross,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CuboidDefinition {
+    /// The corner with the lowest coordinates, in model units.
+    pub from: (f32, f32, f32),
+
+    /// The corner with the highest coordinates, in model units.
+    pub to: (f32, f32, f32),
+
+    /// The part of the texture a face shows, as `(min_u, min_v, max_u, max_v)` in model units, keyed by face name
+    /// (`top`, `bottom`, `north`, `south`, `east` or `west`). Faces that aren't listed show the part of the texture
+    /// that lines up with where they are in the block, so the sides of a slab show the lower half of the texture.
+    #[serde(default)]
+    pub uvs: BTreeMap<String, (f32, f32, f32, f32)>,
+}
+
+impl BlockModelDefinition {
+    /// Checks values that parse fine but don't make sense, returning a description of the first problem.
+    pub fn validate(&self) -> Result<(), String> {
+        let BlockModelDefinition::Cuboids(cuboids) = self else {
+            return Ok(());
+        };
+
+        if cuboids.is_empty() {
+            return Err("a cuboid model needs at least one cuboid".to_owned());
+        }
+
+        for cuboid in cuboids.iter() {
+            let from = Vec3::from(cuboid.from);
+            let to = Vec3::from(cuboid.to);
+
+            let inside_block =
+                from.cmpge(Vec3::ZERO).all() && to.cmple(Vec3::splat(MODEL_UNITS)).all();
+
+            if !inside_block || !from.cmplt(to).all() {
+                return Err(format!(
+                    "cuboid from {:?} to {:?} doesn't lie inside of the block, or has no volume",
+                    cuboid.from, cuboid.to
+                ));
+            }
+
+            if let Some(face) = cuboid
+                .uvs
+                .keys()
+                .find(|face| !FACE_NAMES.contains(&face.as_str()))
+            {
+                return Err(format!("cuboid UVs refer to unknown face \"{}\"", face));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The shape of a block state, turned along with the state.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockModel {
+    Cube,
+    Cuboids(Vec<Cuboid>),
+    Cross,
+}
+
+/// A box inside of a block, in block units from 0 to 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cuboid {
+    pub min: Vec3,
+    pub max: Vec3,
+
+    /// The part of the texture each face shows, in the order of [`mesh::NEIGHBOR_DATA`], as
+    /// `[min_u, min_v, max_u, max_v]` where 1 is the width of the texture. `None` lines the texture up with where the
+    /// face is in the block.
+    pub uvs: [Option<[f32; 4]>; 6],
+}
+
+impl Cuboid {
+    pub const FULL_BLOCK: Self = Self {
+        min: Vec3::ZERO,
+        max: Vec3::ONE,
+        uvs: [None; 6],
+    };
+
+    /// What players can target inside of a block with a cross model. Plants are thinner than the block, and don't
+    /// reach all the way up.
+    pub const PLANT: Self = Self {
+        min: Vec3::new(0.125, 0.0, 0.125),
+        max: Vec3::new(0.875, 0.8125, 0.875),
+        uvs: [None; 6],
+    };
+
+    /// Whether the face pointing in the direction of the [`mesh::NEIGHBOR_DATA`] entry lies on the side of the block.
+    pub fn is_on_block_side(&self, face_index: usize) -> bool {
+        let ((dx, dy, dz), ..) = mesh::NEIGHBOR_DATA[face_index];
+        let direction = IVec3::new(dx, dy, dz);
+        let axis = mesh::normal_axis(direction);
+
+        if direction[axis] > 0 {
+            self.max[axis] >= 1.0
+        } else {
+            self.min[axis] <= 0.0
+        }
+    }
+
+    /// The UVs of the face's vertices, in the vertex order of the [`mesh::NEIGHBOR_DATA`] entry.
+    pub fn face_uvs(&self, face_index: usize) -> [[f32; 2]; 4] {
+        let (_, geometry, _, uvs) = mesh::NEIGHBOR_DATA[face_index];
+
+        if let Some([min_u, min_v, max_u, max_v]) = self.uvs[face_index] {
+            return uvs.map(|[u, v]| [min_u + (max_u - min_u) * u, min_v + (max_v - min_v) * v]);
+        }
+
+        // The texture of a full block face, restricted to the part this face covers
+        let (u_direction, v_direction) = mesh::uv_directions(geometry, uvs);
+        let origin = uvs
+            .iter()
+            .position(|&uv| uv == [0.0, 0.0])
+            .map(|index| Vec3::from(geometry[index]))
+            .unwrap();
+
+        geometry.map(|vertex| {
+            let position = self.min + Vec3::from(vertex) * (self.max - self.min) - origin;
+
+            [
+                position.dot(u_direction.as_vec3()),
+                position.dot(v_direction.as_vec3()),
+            ]
+        })
+    }
+
+    fn from_definition(definition: &CuboidDefinition) -> Self {
+        let uvs = FACE_NAMES.map(|face| {
+            definition
+                .uvs
+                .get(face)
+                .map(|&(min_u, min_v, max_u, max_v)| {
+                    [min_u, min_v, max_u, max_v].map(|uv| uv / MODEL_UNITS)
+                })
+        });
+
+        Self {
+            min: Vec3::from(definition.from) / MODEL_UNITS,
+            max: Vec3::from(definition.to) / MODEL_UNITS,
+            uvs,
+        }
+    }
+
+    /// Turns the cuboid around the center of the block.
+    fn rotated(&self, rotation: BlockRotation) -> Self {
+        // Quarter turns only swap and negate axes, so rounding removes the error of the sines and cosines and
+        // cuboids that touch the side of the block keep touching it
+        let matrix = Mat3::from_cols_array(&rotation.matrix().to_cols_array().map(f32::round));
+        let rotate = |position: Vec3| matrix * (position - 0.5) + 0.5;
+
+        let (a, b) = (rotate(self.min), rotate(self.max));
+
+        // The UVs move to the face that points where the face they belonged to ended up
+        let mut uvs = [None; 6];
+        for (face_index, ((dx, dy, dz), ..)) in mesh::NEIGHBOR_DATA.into_iter().enumerate() {
+            let direction = matrix * Vec3::new(dx as f32, dy as f32, dz as f32);
+            uvs[mesh::face_index(direction.as_ivec3())] = self.uvs[face_index];
+        }
+
+        Self {
+            min: a.min(b),
+            max: a.max(b),
+            uvs,
+        }
+    }
+}
+
+impl BlockModel {
+    pub fn new(definition: &BlockModelDefinition, rotation: BlockRotation) -> Self {
+        match definition {
+            BlockModelDefinition::Cube => BlockModel::Cube,
+            BlockModelDefinition::Cuboids(cuboids) => BlockModel::Cuboids(
+                cuboids
+                    .iter()
+                    .map(|cuboid| Cuboid::from_definition(cuboid).rotated(rotation))
+                    .collect(),
+            ),
+            BlockModelDefinition::Cross => BlockModel::Cross,
+        }
+    }
+
+    pub fn is_cube(&self) -> bool {
+        matches!(self, BlockModel::Cube)
+    }
+
+    /// Whether the face pointing in each direction of [`mesh::NEIGHBOR_DATA`] covers the whole side of the block,
+    /// hiding the face of the neighbor behind it. Faces of several cuboids can cover the side together.
+    pub fn full_faces(&self) -> [bool; 6] {
+        let cuboids = match self {
+            BlockModel::Cube => return [true; 6],
+            BlockModel::Cuboids(cuboids) => cuboids,
+            BlockModel::Cross => return [false; 6],
+        };
+
+        std::array::from_fn(|face_index| {
+            let ((dx, dy, dz), ..) = mesh::NEIGHBOR_DATA[face_index];
+            let (u_axis, v_axis) = mesh::uv_axes(mesh::normal_axis(IVec3::new(dx, dy, dz)));
+
+            let units = MODEL_UNITS as usize;
+            let mut covered = vec![false; units * units];
+
+            for cuboid in cuboids
+                .iter()
+                .filter(|cuboid| cuboid.is_on_block_side(face_index))
+            {
+                for v in 0..units {
+                    for u in 0..units {
+                        // Check the center of every model unit on the side
+                        let center = (Vec2::new(u as f32, v as f32) + 0.5) / MODEL_UNITS;
+
+                        covered[v * units + u] |= (cuboid.min[u_axis]..cuboid.max[u_axis])
+                            .contains(&center.x)
+                            && (cuboid.min[v_axis]..cuboid.max[v_axis]).contains(&center.y);
+                    }
+                }
+            }
+
+            covered.into_iter().all(|covered| covered)
+        })
+    }
+
+    /// The boxes that entities collide with.
+    pub fn collision_boxes(&self) -> &[Cuboid] {
+        match self {
+            BlockModel::Cube => std::slice::from_ref(&Cuboid::FULL_BLOCK),
+            BlockModel::Cuboids(cuboids) => cuboids,
+            BlockModel::Cross => &[],
+        }
+    }
+
+    /// The boxes that raycasts hit, so players can target blocks they walk through.
+    pub fn hit_boxes(&self) -> &[Cuboid] {
+        match self {
+            BlockModel::Cross => std::slice::from_ref(&Cuboid::PLANT),
+            _ => self.collision_boxes(),
+        }
+    }
+}
+
+/// The two diagonal quads of a cross model, in the vertex order of [`mesh::faces`], with the normal of their front.
+/// Their back is drawn with the vertices mirrored.
+pub const CROSS_QUADS: [([[f32; 3]; 4], [f32; 3]); 2] = [
+    (
+        [
+            [0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [1.0, 0.0, 1.0],
+            [1.0, 1.0, 1.0],
+        ],
+        [
+            -std::f32::consts::FRAC_1_SQRT_2,
+            0.0,
+            std::f32::consts::FRAC_1_SQRT_2,
+        ],
+    ),
+    (
+        [
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 0.0, 1.0],
+            [0.0, 1.0, 1.0],
+        ],
+        [
+            -std::f32::consts::FRAC_1_SQRT_2,
+            0.0,
+            -std::f32::consts::FRAC_1_SQRT_2,
+        ],
+    ),
+];
+
+/// The UVs of both sides of a cross quad, so the texture isn't mirrored on either of them.
+pub const CROSS_UVS: [[f32; 2]; 4] = [[0.0, 1.0], [0.0, 0.0], [1.0, 1.0], [1.0, 0.0]];
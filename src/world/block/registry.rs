@@ -2,64 +2,74 @@ use std::{num::NonZeroU16, sync::Arc};
 
 use bevy::{prelude::*, render::render_asset::RenderAssetUsages, utils::HashMap};
 
+use crate::mods::{self, ModContent};
+
 use super::{
-    excavatemanufacturate_blocks, static_block_data::StaticBlockData, Block, BlockId, BlockName,
+    atlas::{self, StitchedAtlas},
+    definition::BlockDefinition,
+    excavatemanufacturate_blocks::block_names,
+    state::BlockState,
+    static_block_data::StaticBlockData,
+    Block, BlockId, BlockName,
 };
 
 #[derive(Resource, Deref)]
 pub struct BlockRegistryResource(Arc<BlockRegistry>);
 
 pub struct BlockRegistry {
+    /// The default state of every block.
     pub block_ids: HashMap<BlockName, BlockId>,
     pub static_block_data: HashMap<BlockId, StaticBlockData>,
     pub atlas_size: (usize, usize),
+
+    states: HashMap<BlockId, BlockState>,
+    state_ids: HashMap<String, BlockId>,
 }
 
 impl BlockRegistry {
-    fn create(atlas_size: (usize, usize)) -> Self {
-        use excavatemanufacturate_blocks::*;
-
+    fn create(definitions: Vec<BlockDefinition>, atlas: &StitchedAtlas) -> Self {
         let mut block_ids = HashMap::new();
         let mut static_block_data = HashMap::new();
-
-        let block_names = [
-            block_names::GRASS,
-            block_names::DIRT,
-            block_names::BEDROCK,
-            block_names::STONE,
-        ];
-
-        for (next_block_id, name) in block_names.into_iter().enumerate() {
-            block_ids.insert(
-                name,
-                BlockId(NonZeroU16::new(next_block_id as u16 + 1).unwrap()),
-            );
+        let mut states = HashMap::new();
+        let mut state_ids = HashMap::new();
+
+        let mut next_block_id: u16 = 1;
+
+        for definition in definitions {
+            for (state_index, properties) in definition.data.states().into_iter().enumerate() {
+                let id = BlockId(NonZeroU16::new(next_block_id).unwrap());
+                next_block_id = next_block_id
+                    .checked_add(1)
+                    .expect("There are more block states than fit into a block ID");
+
+                // The first state is the default state
+                if state_index == 0 {
+                    block_ids.insert(definition.name.clone(), id);
+                }
+
+                static_block_data.insert(
+                    id,
+                    definition
+                        .data
+                        .static_block_data(&properties, |texture| atlas.get(texture)),
+                );
+
+                let state = BlockState::new(definition.name.clone(), properties);
+                state_ids.insert(state.name().to_owned(), id);
+                states.insert(id, state);
+            }
         }
 
-        static_block_data.insert(
-            *block_ids.get(&block_names::GRASS).unwrap(),
-            excavatemanufacturate_blocks::block_data::GRASS,
-        );
-        static_block_data.insert(
-            *block_ids.get(&block_names::DIRT).unwrap(),
-            excavatemanufacturate_blocks::block_data::DIRT,
-        );
-        static_block_data.insert(
-            *block_ids.get(&block_names::BEDROCK).unwrap(),
-            excavatemanufacturate_blocks::block_data::BEDROCK,
-        );
-        static_block_data.insert(
-            *block_ids.get(&block_names::STONE).unwrap(),
-            excavatemanufacturate_blocks::block_data::STONE,
-        );
-
         Self {
             block_ids,
             static_block_data,
-            atlas_size,
+            atlas_size: atlas.size(),
+            states,
+            state_ids,
         }
     }
 
+    /// Creates a block in its default state.
     pub fn create_block(&self, name: &BlockName) -> Option<Block> {
         self.get_block_id(name).map(|id| Block {
             id,
@@ -67,81 +77,129 @@ impl BlockRegistry {
         })
     }
 
+    /// The ID of the block's default state.
     pub fn get_block_id(&self, name: &BlockName) -> Option<BlockId> {
         self.block_ids.get(name).cloned()
     }
 
-    pub fn get_block_data(&self, id: BlockId) -> &StaticBlockData {
+    /// The ID of a state by its full name, see [`BlockState::state_name`].
+    pub fn get_state_id(&self, state_name: &str) -> Option<BlockId> {
+        self.state_ids.get(state_name).cloned()
+    }
+
+    pub fn get_state(&self, id: BlockId) -> &BlockState {
         // It's ok to panic here because BlockId is never manually created; it should always be valid.
-        self.static_block_data
+        self.states
             .get(&id)
             .unwrap_or_else(|| panic!("Block id {:?} doesn't exist in the block registry", id))
     }
-}
-
-#[derive(Resource, Deref, DerefMut)]
-pub struct TextureAtlasHandle(Handle<Image>);
 
-#[derive(Resource, Deref, DerefMut)]
-pub struct Mods(Vec<ModInfo>);
-
-pub struct ModInfo {
-    pub namespace: &'static str,
-    pub blocks: Vec<&'static str>,
-}
+    /// Every state of every block, by their full names.
+    pub fn states(&self) -> impl Iterator<Item = (&str, BlockId)> {
+        self.state_ids
+            .iter()
+            .map(|(state_name, &id)| (state_name.as_str(), id))
+    }
 
-pub fn setup(mut commands: Commands, mut assets: ResMut<Assets<Image>>) {
-    let mut assets_directory = bevy::asset::io::file::FileAssetReader::get_base_path();
-    assets_directory.push("assets");
-    assets_directory.push("excavatemanufacturate");
-    assets_directory.push("textures");
+    /// The state of the same block with one property changed. Returns `None` if the block doesn't have that property
+    /// or value.
+    pub fn with_property(&self, id: BlockId, property: &str, value: &str) -> Option<BlockId> {
+        let state = self.get_state(id);
+        state.get_property(property)?;
+
+        let properties: Vec<_> = state
+            .properties
+            .iter()
+            .map(|(name, current_value)| {
+                let value = if name == property {
+                    value
+                } else {
+                    current_value
+                };
+                (name.clone(), value.to_owned())
+            })
+            .collect();
+
+        self.get_state_id(&BlockState::state_name(&state.block, &properties))
+    }
 
-    // let mut block_textures = Vec::new();
+    /// Whether both IDs are states of the same block.
+    pub fn is_same_block(&self, a: BlockId, b: BlockId) -> bool {
+        a == b || self.get_state(a).block == self.get_state(b).block
+    }
 
-    // for mod_info in mods.iter() {
-    //     let mut directory = assets_directory.join(mod_info.namespace);
-    //     directory.push("textures");
-    //     directory.push("block");
+    pub fn get_block_data(&self, id: BlockId) -> &StaticBlockData {
+        // It's ok to panic here because BlockId is never manually created; it should always be valid.
+        self.static_block_data
+            .get(&id)
+            .unwrap_or_else(|| panic!("Block id {:?} doesn't exist in the block registry", id))
+    }
+}
 
-    //     for &block in mod_info.blocks.iter() {
-    //         let texture_file = directory.join(format!("{}.png", block));
+#[cfg(test)]
+impl BlockRegistry {
+    /// Creates a registry of blocks in the `test` namespace from their names and the contents of their definition
+    /// files. Every block uses the placeholder texture.
+    pub fn for_tests(definitions: &[(&str, &str)]) -> Self {
+        let definitions = definitions
+            .iter()
+            .map(|&(name, data)| {
+                BlockDefinition::new("test", name, ron::from_str(data).unwrap()).unwrap()
+            })
+            .collect();
+        let atlas = atlas::stitch_atlas(std::path::Path::new(""), [], HashMap::new());
+
+        Self::create(definitions, &atlas)
+    }
+}
 
-    //         let Ok(image) = image::open(texture_file) else {
-    //             panic!(
-    //                 "Failed to read image when parsing textures for mod {}: {}",
-    //                 mod_info.namespace, block
-    //             );
-    //         };
+#[derive(Resource, Deref, DerefMut)]
+pub struct TextureAtlasHandle(Handle<Image>);
 
-    //         block_textures.push(image);
-    //     }
-    // }
+pub fn setup(
+    mut commands: Commands,
+    mut assets: ResMut<Assets<Image>>,
+    mut mod_content: ResMut<ModContent>,
+) {
+    let definitions = std::mem::take(&mut mod_content.blocks);
+
+    // The game itself places these blocks, so it can't run without them
+    let missing_blocks: Vec<_> = block_names::ALL
+        .into_iter()
+        .filter(|name| {
+            !definitions
+                .iter()
+                .any(|definition| definition.name == *name)
+        })
+        .map(|name| name.0)
+        .collect();
 
-    // let namespaces = ["excavatemanufacturate"];
+    if !missing_blocks.is_empty() {
+        panic!(
+            "Missing definitions for built-in blocks: {}, see the mod conflicts above",
+            missing_blocks.join(", ")
+        );
+    }
 
-    // let excavatemanufacturate = ModInfo {
-    //     namespace: "excavatemanufacturate",
-    //     blocks: vec!["grass, dirt, bedrock, stone"],
-    // };
+    info!("Loaded {} block definitions", definitions.len());
 
-    // for namespace in namespaces {
-    //     let mut directory = assets_directory.join(namespace);
-    //     directory.push("textures");
-    //     directory.push("block");
-    // }
+    let atlas = atlas::stitch_atlas(
+        &mods::assets_directory(),
+        definitions
+            .iter()
+            .flat_map(|definition| definition.data.texture_names())
+            .map(String::as_str),
+        std::mem::take(&mut mod_content.textures),
+    );
 
-    let atlas_dynamic_image =
-        image::open(assets_directory.join("atlas.png")).expect("Couldn't load texture atlas image");
+    let block_registry = BlockRegistry::create(definitions, &atlas);
 
-    let atlas_size = (
-        atlas_dynamic_image.width() as usize,
-        atlas_dynamic_image.height() as usize,
+    let atlas_image = Image::from_dynamic(
+        image::DynamicImage::ImageRgba8(atlas.image),
+        true,
+        RenderAssetUsages::RENDER_WORLD,
     );
-
-    let atlas_image =
-        Image::from_dynamic(atlas_dynamic_image, true, RenderAssetUsages::RENDER_WORLD);
     commands.insert_resource(TextureAtlasHandle(assets.add(atlas_image)));
 
-    let block_registry = BlockRegistry::create(atlas_size);
     commands.insert_resource(BlockRegistryResource(Arc::new(block_registry)));
 }
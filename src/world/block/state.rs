@@ -0,0 +1,52 @@
+use super::BlockName;
+
+/// One combination of property values of a block, such as a log lying along the x axis. Every state has its own
+/// [`super::BlockId`], so changing a property means changing the block's ID.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockState {
+    pub block: BlockName,
+
+    /// The value of each property, in the order the block's definition declares them.
+    pub properties: Vec<(String, String)>,
+
+    /// The full name of the state, see [`BlockState::state_name`].
+    name: String,
+}
+
+impl BlockState {
+    pub fn new(block: BlockName, properties: Vec<(String, String)>) -> Self {
+        let name = Self::state_name(&block, &properties);
+
+        Self {
+            block,
+            properties,
+            name,
+        }
+    }
+
+    /// Names a state like `excavatemanufacturate/log[axis=x]`. Blocks without properties are named like the block
+    /// itself.
+    pub fn state_name(block: &BlockName, properties: &[(String, String)]) -> String {
+        if properties.is_empty() {
+            return block.0.to_string();
+        }
+
+        let properties: Vec<_> = properties
+            .iter()
+            .map(|(property, value)| format!("{}={}", property, value))
+            .collect();
+
+        format!("{}[{}]", block.0, properties.join(","))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_property(&self, property: &str) -> Option<&str> {
+        self.properties
+            .iter()
+            .find(|(name, _)| name == property)
+            .map(|(_, value)| value.as_str())
+    }
+}
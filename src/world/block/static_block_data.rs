@@ -1,43 +1,272 @@
-use crate::util::mesh::BlockFace;
+use bevy::prelude::*;
+use serde::Deserialize;
 
-#[derive(Clone, Copy)]
+use crate::{
+    util::mesh::{self, UvTransform},
+    world::light::MAX_LIGHT_LEVEL,
+};
+
+use super::model::BlockModel;
+
+/// A rectangle of pixels in the texture atlas, with both corners inclusive.
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct AtlasCoordinates {
     pub min: (u16, u16),
     pub max: (u16, u16),
 }
 
-/// Contains coordinate data for each block. If only the top texture is specified, that texture is used for
-/// all faces of the block.
-pub struct BlockTextures {
-    pub top: AtlasCoordinates,
+/// Contains the texture of each face of a block. If only the top texture is specified, that texture is used for
+/// all faces of the block. Any of the four horizontal faces can be given its own texture, the others fall back to
+/// `sides`. Block definitions name their textures, which become atlas coordinates once the atlas is stitched.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BlockTextures<T = AtlasCoordinates> {
+    pub top: FaceTexture<T>,
 
     /// Defaults to top face coordinates if not set
-    pub sides: Option<AtlasCoordinates>,
+    #[serde(default = "Option::default")]
+    pub sides: Option<FaceTexture<T>>,
 
     /// Defaults to top face coordinates if not set
-    pub bottom: Option<AtlasCoordinates>,
+    #[serde(default = "Option::default")]
+    pub bottom: Option<FaceTexture<T>>,
+
+    /// The face towards negative z. Defaults to the side texture if not set
+    #[serde(default = "Option::default")]
+    pub north: Option<FaceTexture<T>>,
+
+    /// The face towards positive z. Defaults to the side texture if not set
+    #[serde(default = "Option::default")]
+    pub south: Option<FaceTexture<T>>,
+
+    /// The face towards positive x. Defaults to the side texture if not set
+    #[serde(default = "Option::default")]
+    pub east: Option<FaceTexture<T>>,
+
+    /// The face towards negative x. Defaults to the side texture if not set
+    #[serde(default = "Option::default")]
+    pub west: Option<FaceTexture<T>>,
 }
 
-impl BlockTextures {
-    /// Creates a [`BlockTextures`] from one set of atlas coordinates.
-    pub const fn from_single(atlas_coordinates: AtlasCoordinates) -> Self {
-        Self {
-            top: atlas_coordinates,
-            sides: None,
-            bottom: None,
+impl<T> BlockTextures<T> {
+    pub fn map<U>(self, mut f: impl FnMut(T) -> U) -> BlockTextures<U> {
+        let mut map_face = |face: FaceTexture<T>| face.map(&mut f);
+
+        BlockTextures {
+            top: map_face(self.top),
+            sides: self.sides.map(&mut map_face),
+            bottom: self.bottom.map(&mut map_face),
+            north: self.north.map(&mut map_face),
+            south: self.south.map(&mut map_face),
+            east: self.east.map(&mut map_face),
+            west: self.west.map(&mut map_face),
         }
     }
 
-    pub fn get_coords(&self, face: BlockFace) -> AtlasCoordinates {
-        match face {
-            BlockFace::Top => self.top,
-            BlockFace::Side => self.sides.unwrap_or(self.top),
-            BlockFace::Bottom => self.bottom.unwrap_or(self.top),
+    fn faces(&self) -> impl Iterator<Item = &FaceTexture<T>> {
+        std::iter::once(&self.top).chain(
+            [
+                &self.sides,
+                &self.bottom,
+                &self.north,
+                &self.south,
+                &self.east,
+                &self.west,
+            ]
+            .into_iter()
+            .flatten(),
+        )
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.faces().map(|face| &face.texture)
+    }
+
+    pub fn as_ref(&self) -> BlockTextures<&T> {
+        BlockTextures {
+            top: self.top.as_ref(),
+            sides: self.sides.as_ref().map(FaceTexture::as_ref),
+            bottom: self.bottom.as_ref().map(FaceTexture::as_ref),
+            north: self.north.as_ref().map(FaceTexture::as_ref),
+            south: self.south.as_ref().map(FaceTexture::as_ref),
+            east: self.east.as_ref().map(FaceTexture::as_ref),
+            west: self.west.as_ref().map(FaceTexture::as_ref),
         }
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+impl<T: Copy> BlockTextures<T> {
+    /// The texture of the face pointing in the direction, which is one of the six unit directions.
+    pub fn get(&self, direction: IVec3) -> FaceTexture<T> {
+        let side = || self.sides.unwrap_or(self.top);
+
+        match direction.to_array() {
+            [0, 1, 0] => self.top,
+            [0, -1, 0] => self.bottom.unwrap_or(self.top),
+            [0, 0, -1] => self.north.unwrap_or_else(side),
+            [0, 0, 1] => self.south.unwrap_or_else(side),
+            [1, 0, 0] => self.east.unwrap_or_else(side),
+            _ => self.west.unwrap_or_else(side),
+        }
+    }
+
+    /// The texture of each face of a block that's turned by `rotation`, in the order of [`mesh::NEIGHBOR_DATA`].
+    /// Textures turn along with the block, so the top texture of a log lying on its side points sideways.
+    pub fn oriented_faces(&self, rotation: BlockRotation) -> [FaceTexture<T>; 6] {
+        let matrix = rotation.matrix();
+        let rotate = |direction: IVec3| (matrix * direction.as_vec3()).round().as_ivec3();
+
+        mesh::NEIGHBOR_DATA.map(|((dx, dy, dz), geometry, _, uvs)| {
+            let world_direction = IVec3::new(dx, dy, dz);
+            let (world_u, world_v) = mesh::uv_directions(geometry, uvs);
+
+            // The face of the unrotated block that ended up facing this way
+            let ((model_dx, model_dy, model_dz), model_geometry, _, model_uvs) =
+                mesh::NEIGHBOR_DATA
+                    .into_iter()
+                    .find(|&((dx, dy, dz), ..)| rotate(IVec3::new(dx, dy, dz)) == world_direction)
+                    .unwrap();
+
+            let (model_u, model_v) = mesh::uv_directions(model_geometry, model_uvs);
+            let (model_u, model_v) = (rotate(model_u), rotate(model_v));
+
+            // Rotations only ever map texture axes onto texture axes, possibly reversed
+            let swap = model_u.dot(world_v) != 0;
+            let (u_along, v_along) = if swap {
+                (world_v, world_u)
+            } else {
+                (world_u, world_v)
+            };
+
+            let rotation_transform = UvTransform {
+                swap,
+                flip_u: model_u.dot(u_along) < 0,
+                flip_v: model_v.dot(v_along) < 0,
+            };
+
+            let model_face = self.get(IVec3::new(model_dx, model_dy, model_dz));
+
+            // The face's own transform is relative to the face, so it applies after turning with the block
+            FaceTexture {
+                texture: model_face.texture,
+                uv_transform: rotation_transform.then(model_face.uv_transform),
+            }
+        })
+    }
+}
+
+/// How a block state is turned, in degrees around the x axis and then around the y axis. Both have to be 0, 90, 180
+/// or 270.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BlockRotation {
+    #[serde(default)]
+    pub x: u16,
+
+    #[serde(default)]
+    pub y: u16,
+}
+
+impl BlockRotation {
+    pub fn is_valid(self) -> bool {
+        is_quarter_turn(self.x) && is_quarter_turn(self.y)
+    }
+
+    pub fn matrix(self) -> Mat3 {
+        Mat3::from_rotation_y((self.y as f32).to_radians())
+            * Mat3::from_rotation_x((self.x as f32).to_radians())
+    }
+}
+
+fn is_quarter_turn(degrees: u16) -> bool {
+    matches!(degrees, 0 | 90 | 180 | 270)
+}
+
+/// The texture on one face of a block, and how it's turned or mirrored on that face.
+///
+/// Definitions write a face either as just the texture name, or as
+/// `(texture: "furnace_front", rotation: 90, flip_u: true)`. The rotation turns the texture clockwise in quarter
+/// turns, the flips then mirror it horizontally or vertically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "FaceTextureDefinition<T>")]
+#[serde(bound(deserialize = "T: Deserialize<'de>"))]
+pub struct FaceTexture<T = AtlasCoordinates> {
+    pub texture: T,
+    pub uv_transform: UvTransform,
+}
+
+impl<T> FaceTexture<T> {
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> FaceTexture<U> {
+        FaceTexture {
+            texture: f(self.texture),
+            uv_transform: self.uv_transform,
+        }
+    }
+
+    pub fn as_ref(&self) -> FaceTexture<&T> {
+        FaceTexture {
+            texture: &self.texture,
+            uv_transform: self.uv_transform,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FaceTextureDefinition<T> {
+    Texture(T),
+    Transformed {
+        texture: T,
+
+        #[serde(default)]
+        rotation: u16,
+
+        #[serde(default)]
+        flip_u: bool,
+
+        #[serde(default)]
+        flip_v: bool,
+    },
+}
+
+impl<T> TryFrom<FaceTextureDefinition<T>> for FaceTexture<T> {
+    type Error = String;
+
+    fn try_from(definition: FaceTextureDefinition<T>) -> Result<Self, Self::Error> {
+        match definition {
+            FaceTextureDefinition::Texture(texture) => Ok(Self {
+                texture,
+                uv_transform: UvTransform::default(),
+            }),
+            FaceTextureDefinition::Transformed {
+                texture,
+                rotation,
+                flip_u,
+                flip_v,
+            } => {
+                if !is_quarter_turn(rotation) {
+                    return Err(format!(
+                        "texture rotation {} isn't a quarter turn",
+                        rotation
+                    ));
+                }
+
+                let mirror = UvTransform {
+                    swap: false,
+                    flip_u,
+                    flip_v,
+                };
+
+                Ok(Self {
+                    texture,
+                    uv_transform: UvTransform::clockwise_rotation(rotation / 90).then(mirror),
+                })
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
 pub enum ToolType {
     Shovel,
     Pickaxe,
@@ -46,14 +275,136 @@ pub enum ToolType {
     Hoe,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize)]
 pub enum BlockHardnessLevel {
     Hand,
     Tool(ToolType, u8),
     Unbreakable,
 }
 
+impl BlockHardnessLevel {
+    /// Seconds it takes to break a block that can be broken by hand, without a tool.
+    const HAND_BREAK_TIME: f32 = 0.5;
+
+    /// Seconds it takes to break a block requiring a tool, using a tool that exactly meets the required tier. Every
+    /// required tier adds this amount again.
+    const TOOL_BREAK_TIME: f32 = 1.5;
+
+    /// How many seconds it takes to break a block of this hardness with the held tool, which is a tool type and its
+    /// tier. Returns `None` if the block can't be broken with the held tool at all.
+    pub fn break_time(self, held_tool: Option<(ToolType, u8)>) -> Option<f32> {
+        match self {
+            // Tools don't help with blocks that don't need them
+            BlockHardnessLevel::Hand => Some(Self::HAND_BREAK_TIME),
+            BlockHardnessLevel::Tool(required_type, required_tier) => {
+                let (tool_type, tier) = held_tool?;
+
+                if tool_type != required_type || tier < required_tier {
+                    return None;
+                }
+
+                // Tools of a higher tier than required break blocks faster
+                let base_time = Self::TOOL_BREAK_TIME * (required_tier as f32 + 1.0);
+                Some(base_time / ((tier - required_tier) as f32 + 1.0))
+            }
+            BlockHardnessLevel::Unbreakable => None,
+        }
+    }
+}
+
+/// How the faces of a block are drawn, and which faces of their neighbors they hide.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+pub enum RenderLayer {
+    /// Textures without see-through pixels. Opaque blocks hide the faces of every neighbor behind them.
+    #[default]
+    Opaque,
+
+    /// Textures with pixels that are either fully see-through or not at all, like plants. Drawn along with opaque
+    /// blocks, leaving out the see-through pixels.
+    Cutout,
+
+    /// Textures that are partly see-through, like tinted glass. Drawn after everything else with alpha blending, in
+    /// a separate mesh.
+    Translucent,
+}
+
+/// Data shared by all blocks of the same state, created from the block's definition file.
 pub struct StaticBlockData {
-    pub textures: BlockTextures,
+    /// The texture of each face, in the order of [`mesh::NEIGHBOR_DATA`].
+    pub faces: [FaceTexture; 6],
+    pub model: BlockModel,
+
+    /// Whether each face covers the whole side of the block, in the order of [`mesh::NEIGHBOR_DATA`]. See
+    /// [`BlockModel::full_faces`].
+    pub full_faces: [bool; 6],
+
     pub hardness: BlockHardnessLevel,
+    pub render_layer: RenderLayer,
+
+    /// Whether light passes through this block.
+    pub transparent: bool,
+
+    /// The light level this block emits, from 0 to [`StaticBlockData::MAX_LIGHT_EMISSION`].
+    pub light_emission: u8,
+}
+
+impl StaticBlockData {
+    pub const MAX_LIGHT_EMISSION: u8 = MAX_LIGHT_LEVEL;
+
+    /// Whether the block is a full cube that can't be seen through, which is what casts ambient occlusion.
+    pub fn is_opaque_cube(&self) -> bool {
+        self.model.is_cube() && self.render_layer == RenderLayer::Opaque
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hand_blocks_ignore_tools() {
+        let hardness = BlockHardnessLevel::Hand;
+
+        assert_eq!(hardness.break_time(None), Some(0.5));
+        assert_eq!(hardness.break_time(Some((ToolType::Pickaxe, 3))), Some(0.5));
+    }
+
+    #[test]
+    fn unbreakable_blocks_never_break() {
+        let hardness = BlockHardnessLevel::Unbreakable;
+
+        assert_eq!(hardness.break_time(None), None);
+        assert_eq!(
+            hardness.break_time(Some((ToolType::Pickaxe, u8::MAX))),
+            None
+        );
+    }
+
+    #[test]
+    fn tool_blocks_need_the_right_tool() {
+        let hardness = BlockHardnessLevel::Tool(ToolType::Pickaxe, 1);
+
+        assert_eq!(hardness.break_time(None), None);
+        assert_eq!(hardness.break_time(Some((ToolType::Shovel, 1))), None);
+        assert_eq!(hardness.break_time(Some((ToolType::Pickaxe, 0))), None);
+
+        assert_eq!(hardness.break_time(Some((ToolType::Pickaxe, 1))), Some(3.0));
+        assert_eq!(hardness.break_time(Some((ToolType::Pickaxe, 2))), Some(1.5));
+        assert_eq!(hardness.break_time(Some((ToolType::Pickaxe, 3))), Some(1.0));
+    }
+
+    #[test]
+    fn highest_tiers_dont_overflow() {
+        let hardness = BlockHardnessLevel::Tool(ToolType::Pickaxe, u8::MAX);
+        assert_eq!(
+            hardness.break_time(Some((ToolType::Pickaxe, u8::MAX))),
+            Some(1.5 * 256.0)
+        );
+
+        let hardness = BlockHardnessLevel::Tool(ToolType::Pickaxe, 0);
+        assert_eq!(
+            hardness.break_time(Some((ToolType::Pickaxe, u8::MAX))),
+            Some(1.5 / 256.0)
+        );
+    }
 }
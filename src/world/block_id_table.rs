@@ -0,0 +1,174 @@
+use std::{collections::BTreeMap, io, path::Path};
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use super::block::{registry::BlockRegistry, Block, BlockData, BlockId, BlockName};
+
+/// The file in a world save that stores its block ID table.
+pub const BLOCK_ID_TABLE_FILE_NAME: &str = "block_ids.ron";
+
+/// Maps the block IDs stored in a world save to the IDs of the current block registry and back.
+///
+/// Registry IDs depend on which blocks exist, so they change whenever a block is added or removed. A save instead
+/// gives every block state it has ever seen its own ID that never changes, and keeps that table on disk next to the
+/// region files, keyed by state name. States that no longer exist load as the default state of their block, and
+/// blocks that no longer exist load as a placeholder.
+pub struct BlockIdTable {
+    /// Every block state the save knows about, including ones that are missing from the registry.
+    save_ids: BTreeMap<String, u16>,
+
+    save_to_registry: HashMap<u16, BlockData>,
+    registry_to_save: HashMap<BlockId, u16>,
+}
+
+impl BlockIdTable {
+    /// Reads the table of the save in `directory` and extends it with blocks that were added to the registry since.
+    /// Saves from before the table existed were written with registry IDs, so those are used as the starting point.
+    /// Blocks that no longer exist are loaded as `missing_block`, or removed if that's `None`.
+    pub fn open(
+        directory: &Path,
+        block_registry: &BlockRegistry,
+        missing_block: Option<&BlockName>,
+    ) -> io::Result<Self> {
+        let path = directory.join(BLOCK_ID_TABLE_FILE_NAME);
+
+        let mut save_ids: BTreeMap<String, u16> = match std::fs::read_to_string(&path) {
+            Ok(contents) => ron::from_str(&contents)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => block_registry
+                .states()
+                .map(|(state_name, id)| (state_name.to_owned(), id.raw()))
+                .collect(),
+            Err(err) => return Err(err),
+        };
+
+        if save_ids.values().any(|&save_id| save_id == 0) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "block ID 0 is reserved for empty space",
+            ));
+        }
+
+        if save_ids.values().collect::<HashSet<_>>().len() != save_ids.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "multiple blocks share the same ID",
+            ));
+        }
+
+        // New blocks get IDs after every ID the save has used so far, sorted so the result doesn't depend on hash
+        // map order
+        let mut new_states: Vec<_> = block_registry
+            .states()
+            .filter(|(state_name, _)| !save_ids.contains_key(*state_name))
+            .collect();
+        new_states.sort_by_key(|&(_, id)| id);
+
+        for (state_name, _) in new_states {
+            let next_save_id = save_ids
+                .values()
+                .max()
+                .map_or(Some(1), |max| max.checked_add(1));
+
+            let Some(next_save_id) = next_save_id else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "the save has run out of block IDs",
+                ));
+            };
+
+            save_ids.insert(state_name.to_owned(), next_save_id);
+        }
+
+        let placeholder = match missing_block {
+            Some(name) => match block_registry.create_block(name) {
+                Some(block) => BlockData::some(block),
+                None => {
+                    warn!(
+                        "Missing block placeholder {} doesn't exist, missing blocks are removed instead",
+                        name.0
+                    );
+                    BlockData::none()
+                }
+            },
+            None => BlockData::none(),
+        };
+
+        let mut save_to_registry = HashMap::new();
+        let mut registry_to_save = HashMap::new();
+        let mut missing_blocks = Vec::new();
+
+        for (state_name, &save_id) in save_ids.iter() {
+            if let Some(id) = block_registry.get_state_id(state_name) {
+                registry_to_save.insert(id, save_id);
+                save_to_registry.insert(
+                    save_id,
+                    BlockData::some(Block {
+                        id,
+                        dynamic_data: None,
+                    }),
+                );
+                continue;
+            }
+
+            // The block may still exist with different properties, then it falls back to its default state
+            let block_name = state_name.split('[').next().unwrap_or(state_name);
+
+            match block_registry.create_block(&BlockName(block_name.to_owned().into())) {
+                Some(block) => {
+                    save_to_registry.insert(save_id, BlockData::some(block));
+                }
+                None => {
+                    save_to_registry.insert(save_id, placeholder.clone());
+                    missing_blocks.push(state_name.clone());
+                }
+            }
+        }
+
+        if !missing_blocks.is_empty() {
+            warn!(
+                "The world save contains {} blocks that no longer exist, they are replaced by {}: {}",
+                missing_blocks.len(),
+                missing_block.map_or("empty space", |name| name.0.as_ref()),
+                missing_blocks.join(", ")
+            );
+        }
+
+        let table = Self {
+            save_ids,
+            save_to_registry,
+            registry_to_save,
+        };
+        table.write(&path)?;
+
+        Ok(table)
+    }
+
+    fn write(&self, path: &Path) -> io::Result<()> {
+        let contents =
+            ron::ser::to_string_pretty(&self.save_ids, ron::ser::PrettyConfig::default())
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        std::fs::write(path, contents)
+    }
+
+    /// The block a stored ID stands for, or `None` if the save never assigned that ID.
+    pub fn decode_block(&self, save_id: u16) -> Option<BlockData> {
+        if save_id == 0 {
+            return Some(BlockData::none());
+        }
+
+        self.save_to_registry.get(&save_id).cloned()
+    }
+
+    /// The ID a block is stored as.
+    pub fn encode_block(&self, block: &BlockData) -> u16 {
+        block.as_ref().map_or(0, |block| {
+            // Every registry block is added to the table when it's opened
+            self.registry_to_save[&block.id]
+        })
+    }
+}
@@ -1,22 +1,57 @@
-use crate::util::{self, block_pos::BlockPos, chunk_pos::ChunkPos, mesh::ChunkMeshBuilder};
+use crate::util::{
+    self,
+    block_pos::BlockPos,
+    chunk_pos::ChunkPos,
+    mesh::{ChunkMeshBuilder, CollisionMeshBuilder, MeshingMode},
+};
+
+use std::{io, sync::Arc};
 
 use super::{
-    block::{registry::BlockRegistry, BlockData},
+    block::{
+        model::{BlockModel, CROSS_QUADS, CROSS_UVS},
+        registry::BlockRegistry,
+        static_block_data::{FaceTexture, RenderLayer},
+        BlockData, BlockId,
+    },
+    block_id_table::BlockIdTable,
+    heightmap::ChunkHeightmap,
+    light::{ChunkLight, LightChannel, MAX_LIGHT_LEVEL},
+    palette::PalettedBlocks,
     world_access::ExcavateManufacturateWorld,
     CHUNK_SIZE,
 };
 use bevy::prelude::*;
 
+pub const CHUNK_VOLUME: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+
+/// What faces need to have in common to be merged by greedy meshing: texture, ambient occlusion, sky and block light,
+/// and render layer.
+type GreedyFaceKey = (FaceTexture, [u8; 4], [u8; 2], RenderLayer);
+
+/// The blocks of a single chunk. Blocks are stored palette-compressed, so a chunk made of a single block type takes
+/// up almost no memory.
+#[derive(Clone)]
 pub struct ChunkData {
-    blocks: Vec<BlockData>,
+    blocks: PalettedBlocks,
     num_blocks: u32,
+
+    /// The light of every block. It isn't saved, but computed again whenever the chunk is loaded, see
+    /// [`super::light`].
+    light: ChunkLight,
+
+    /// The highest solid block of every column. Like light, it's computed again whenever the chunk is loaded, see
+    /// [`super::heightmap`].
+    heightmap: ChunkHeightmap,
 }
 
 impl ChunkData {
     pub fn empty() -> Self {
         Self {
-            blocks: vec![BlockData::none(); CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE],
+            blocks: PalettedBlocks::uniform(CHUNK_VOLUME, BlockData::none()),
             num_blocks: 0,
+            light: ChunkLight::dark(),
+            heightmap: ChunkHeightmap::empty(),
         }
     }
 
@@ -26,26 +61,25 @@ impl ChunkData {
     {
         let mut num_blocks = 0;
 
-        let blocks = (0..(CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE))
-            .map(|i| {
-                let data = supplier(BlockPos::from(Self::deindexify(i)));
-                num_blocks += data.is_some() as u32; // If the block isn't empty, increment num_blocks
+        let blocks = PalettedBlocks::from_fn(CHUNK_VOLUME, |i| {
+            let data = supplier(BlockPos::from(Self::deindexify(i)));
+            num_blocks += data.is_some() as u32; // If the block isn't empty, increment num_blocks
 
-                data
-            })
-            .collect();
+            data
+        });
 
-        Self { blocks, num_blocks }
+        Self {
+            blocks,
+            num_blocks,
+            light: ChunkLight::dark(),
+            heightmap: ChunkHeightmap::empty(),
+        }
     }
 
     pub fn is_empty(&self) -> bool {
         self.num_blocks == 0
     }
 
-    pub fn get_raw_array(&self) -> &[BlockData] {
-        &self.blocks
-    }
-
     pub fn indexify(offset: IVec3) -> usize {
         (offset.z as usize * CHUNK_SIZE * CHUNK_SIZE)
             + (offset.y as usize * CHUNK_SIZE)
@@ -62,87 +96,347 @@ impl ChunkData {
 
     pub fn get(&self, block_pos: BlockPos) -> &BlockData {
         let offset = block_pos.as_chunk_offset().inner();
-        &self.blocks[Self::indexify(offset)]
+        self.blocks.get(Self::indexify(offset))
     }
 
     pub fn set(&mut self, block_pos: BlockPos, block: BlockData) {
-        if self.get(block_pos).is_some() {
+        let block_is_some = block.is_some();
+
+        let offset = block_pos.as_chunk_offset().inner();
+        let previous_block = self.blocks.set(Self::indexify(offset), block);
+
+        if previous_block.is_some() {
             // The existing block is being replaced by air.
-            if block.is_none() {
+            if !block_is_some {
                 self.num_blocks -= 1;
             }
-        } else if block.is_some() {
+        } else if block_is_some {
             // A block is being placed in an empty spot, increasing the total amount.
             self.num_blocks += 1;
         }
+    }
 
+    pub fn get_light(&self, block_pos: BlockPos, channel: LightChannel) -> u8 {
         let offset = block_pos.as_chunk_offset().inner();
-        self.blocks[Self::indexify(offset)] = block;
+        self.light.get(Self::indexify(offset), channel)
+    }
+
+    pub fn set_light(&mut self, block_pos: BlockPos, channel: LightChannel, level: u8) {
+        let offset = block_pos.as_chunk_offset().inner();
+        self.light.set(Self::indexify(offset), channel, level);
+    }
+
+    /// Sets the light of the channel to the same level for every block in the chunk.
+    pub fn fill_light(&mut self, channel: LightChannel, level: u8) {
+        self.light.fill(channel, level);
+    }
+
+    pub fn heightmap(&self) -> &ChunkHeightmap {
+        &self.heightmap
+    }
+
+    pub fn heightmap_mut(&mut self) -> &mut ChunkHeightmap {
+        &mut self.heightmap
     }
 
     pub fn get_from_raw_offset(&self, offset: IVec3) -> &BlockData {
-        &self.blocks[Self::indexify(offset)]
+        self.blocks.get(Self::indexify(offset))
     }
 
     pub fn try_get_from_raw_offset(&self, offset: IVec3) -> Option<&BlockData> {
-        self.blocks.get(Self::indexify(offset))
+        let index = Self::indexify(offset);
+        (index < CHUNK_VOLUME).then(|| self.blocks.get(index))
     }
 
+    /// Encodes the blocks of this chunk for saving. Dynamic block data isn't part of the encoding.
+    pub fn encode(&self, block_ids: &BlockIdTable) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.blocks.encode(&mut bytes, block_ids);
+
+        bytes
+    }
+
+    /// Decodes a chunk that was encoded with [`ChunkData::encode`].
+    pub fn decode(bytes: &[u8], block_ids: &BlockIdTable) -> io::Result<Self> {
+        let blocks = PalettedBlocks::decode(CHUNK_VOLUME, bytes, block_ids)?;
+        let num_blocks = blocks.count(|block| block.is_some());
+
+        Ok(Self {
+            blocks,
+            num_blocks,
+            light: ChunkLight::dark(),
+            heightmap: ChunkHeightmap::empty(),
+        })
+    }
+
+    /// Builds the meshes of this chunk, and the collision geometry of its blocks. The neighborhood provides the
+    /// blocks around the chunk, which are needed for face culling and ambient occlusion at the chunk borders.
     pub fn get_mesh(
         &self,
-        chunk_pos: ChunkPos,
+        neighborhood: &ChunkNeighborhood,
+        block_registry: &BlockRegistry,
+        meshing_mode: MeshingMode,
+    ) -> ChunkGeometry {
+        match meshing_mode {
+            MeshingMode::Naive => self.get_mesh_naive(neighborhood, block_registry),
+            MeshingMode::Greedy => self.get_mesh_greedy(neighborhood, block_registry),
+        }
+    }
+
+    /// Gets the block at the offset, which may lie outside of this chunk. Returns `None` if the chunk containing it
+    /// doesn't exist.
+    fn get_neighborhood_block<'a>(
+        &'a self,
+        neighborhood: &'a ChunkNeighborhood,
+        offset: IVec3,
+    ) -> Option<&'a BlockData> {
+        let exists_in_chunk = offset.cmpge(IVec3::splat(0)).all()
+            && offset.cmple(IVec3::splat(CHUNK_SIZE as i32 - 1)).all();
+
+        if exists_in_chunk {
+            self.try_get_from_raw_offset(offset)
+        } else {
+            // Access the neighboring chunks
+            neighborhood.get_block(offset)
+        }
+    }
+
+    /// Whether there is an opaque full cube at the offset, which may lie outside of this chunk. Only those cast
+    /// ambient occlusion.
+    fn is_solid(
+        &self,
+        neighborhood: &ChunkNeighborhood,
+        offset: IVec3,
+        block_registry: &BlockRegistry,
+    ) -> bool {
+        self.get_neighborhood_block(neighborhood, offset)
+            .and_then(|block_data| block_data.as_ref())
+            .is_some_and(|block| block_registry.get_block_data(block.id).is_opaque_cube())
+    }
+
+    /// Whether the face of the block at the offset that points in the direction can be seen. The neighbor in that
+    /// direction hides the face if it covers the whole side it shares with the block, and either can't be seen
+    /// through or is the same block: glass next to glass hides the face between them, but stone next to glass doesn't.
+    fn is_face_visible(
+        &self,
+        neighborhood: &ChunkNeighborhood,
+        offset: IVec3,
+        direction: IVec3,
+        block_id: BlockId,
+        block_registry: &BlockRegistry,
+    ) -> bool {
+        let neighbor = self
+            .get_neighborhood_block(neighborhood, offset + direction)
+            .and_then(|block_data| block_data.as_ref());
+
+        let Some(neighbor) = neighbor else {
+            return true;
+        };
+
+        let neighbor_data = block_registry.get_block_data(neighbor.id);
+
+        if !neighbor_data.full_faces[util::mesh::face_index(-direction)] {
+            return true;
+        }
+
+        neighbor_data.render_layer != RenderLayer::Opaque
+            && !block_registry.is_same_block(block_id, neighbor.id)
+    }
+
+    /// Computes the ambient occlusion level of each vertex of a face. Every vertex looks at the three blocks touching
+    /// it in front of the face: the two along the face's edges and the one diagonally across. A vertex with both
+    /// edge blocks present is fully occluded, no matter the diagonal block.
+    fn get_face_ao(
+        &self,
+        neighborhood: &ChunkNeighborhood,
+        offset: IVec3,
+        direction: IVec3,
+        geometry: [[f32; 3]; 4],
+        block_registry: &BlockRegistry,
+    ) -> [u8; 4] {
+        let (u_axis, v_axis) = util::mesh::uv_axes(util::mesh::normal_axis(direction));
+        let front = offset + direction;
+
+        geometry.map(|vertex| {
+            // Step towards the side of the block this vertex lies on
+            let mut u_step = IVec3::ZERO;
+            u_step[u_axis] = if vertex[u_axis] > 0.5 { 1 } else { -1 };
+            let mut v_step = IVec3::ZERO;
+            v_step[v_axis] = if vertex[v_axis] > 0.5 { 1 } else { -1 };
+
+            let side_u = self.is_solid(neighborhood, front + u_step, block_registry);
+            let side_v = self.is_solid(neighborhood, front + v_step, block_registry);
+
+            if side_u && side_v {
+                return 0;
+            }
+
+            let corner = self.is_solid(neighborhood, front + u_step + v_step, block_registry);
+
+            ChunkMeshBuilder::AO_UNOCCLUDED - (side_u as u8 + side_v as u8 + corner as u8)
+        })
+    }
+
+    /// Adds a block that isn't a full cube. Model faces are never merged, and only the ones on the side of the block
+    /// are culled by neighbors or darkened by ambient occlusion.
+    fn add_block_model(
+        &self,
+        neighborhood: &ChunkNeighborhood,
+        offset: IVec3,
+        block_id: BlockId,
+        block_registry: &BlockRegistry,
+        chunk_geometry: &mut ChunkGeometry,
+    ) {
+        let static_block_data = block_registry.get_block_data(block_id);
+
+        for cuboid in static_block_data.model.collision_boxes() {
+            chunk_geometry
+                .collision
+                .add_box(offset.as_vec3() + cuboid.min, offset.as_vec3() + cuboid.max);
+        }
+
+        let mesh_builder = chunk_geometry.mesh_builder(static_block_data.render_layer);
+
+        match &static_block_data.model {
+            BlockModel::Cube => {}
+            BlockModel::Cuboids(cuboids) => {
+                for cuboid in cuboids.iter() {
+                    for (face_index, ((dx, dy, dz), geometry, normals, _)) in
+                        util::mesh::NEIGHBOR_DATA.into_iter().enumerate()
+                    {
+                        let direction = IVec3::new(dx, dy, dz);
+
+                        let ao = if cuboid.is_on_block_side(face_index) {
+                            if !self.is_face_visible(
+                                neighborhood,
+                                offset,
+                                direction,
+                                block_id,
+                                block_registry,
+                            ) {
+                                continue;
+                            }
+
+                            self.get_face_ao(
+                                neighborhood,
+                                offset,
+                                direction,
+                                geometry,
+                                block_registry,
+                            )
+                        } else {
+                            [ChunkMeshBuilder::AO_UNOCCLUDED; 4]
+                        };
+
+                        mesh_builder.add_quad(
+                            geometry,
+                            normals,
+                            cuboid.face_uvs(face_index),
+                            offset.as_vec3() + cuboid.min,
+                            cuboid.max - cuboid.min,
+                            Vec2::ONE,
+                            static_block_data.faces[face_index],
+                            block_registry.atlas_size,
+                            ao,
+                            neighborhood.get_light(offset + direction),
+                        );
+                    }
+                }
+            }
+            BlockModel::Cross => {
+                let face_texture = static_block_data.faces[util::mesh::face_index(IVec3::NEG_Z)];
+
+                for (quad, normal) in CROSS_QUADS {
+                    // The back of the quad, with the left and right vertices exchanged
+                    let back = [quad[2], quad[3], quad[0], quad[1]];
+                    let back_normal = normal.map(|component| -component);
+
+                    for (geometry, normal) in [(quad, normal), (back, back_normal)] {
+                        mesh_builder.add_face(
+                            geometry,
+                            [normal; 4],
+                            CROSS_UVS,
+                            offset.as_vec3(),
+                            1.0,
+                            face_texture,
+                            block_registry.atlas_size,
+                            [ChunkMeshBuilder::AO_UNOCCLUDED; 4],
+                            // Plants are lit by the light inside of their own block
+                            neighborhood.get_light(offset),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn get_mesh_naive(
+        &self,
+        neighborhood: &ChunkNeighborhood,
         block_registry: &BlockRegistry,
-        world: &ExcavateManufacturateWorld,
-    ) -> Mesh {
-        let mut mesh_builder = ChunkMeshBuilder::new();
+    ) -> ChunkGeometry {
+        let mut chunk_geometry = ChunkGeometry::new();
 
         for x in 0..(CHUNK_SIZE as i32) {
             for y in 0..(CHUNK_SIZE as i32) {
                 for z in 0..(CHUNK_SIZE as i32) {
                     let offset = IVec3::new(x, y, z);
-                    let index = Self::indexify(offset);
-
-                    let block = &self.blocks[index];
+                    let block = self.get_from_raw_offset(offset);
 
                     let Some(block_type) = block.as_ref() else {
                         continue;
                     };
 
-                    for ((dx, dy, dz), geometry, normals, uvs, face) in util::mesh::NEIGHBOR_DATA {
-                        let neighbor_pos = offset + IVec3::new(dx, dy, dz);
+                    let static_block_data = block_registry.get_block_data(block_type.id);
 
-                        let neighbor_exists_in_chunk = neighbor_pos.cmpge(IVec3::splat(0)).all()
-                            && neighbor_pos
-                                .cmple(IVec3::splat(CHUNK_SIZE as i32 - 1))
-                                .all();
+                    if !static_block_data.model.is_cube() {
+                        self.add_block_model(
+                            neighborhood,
+                            offset,
+                            block_type.id,
+                            block_registry,
+                            &mut chunk_geometry,
+                        );
+                        continue;
+                    }
 
-                        let add_face = if neighbor_exists_in_chunk {
-                            // We can get occlusion info from the chunk data itself
-                            self.try_get_from_raw_offset(neighbor_pos)
-                                .is_some_and(|block_data| block_data.is_none())
-                        } else {
-                            let world_neighbor_pos = BlockPos::from(chunk_pos)
-                                + BlockPos::from(offset)
-                                + BlockPos::new(dx, dy, dz);
-
-                            // Access the world data structure for occlusion test
-                            // Equivalent to, like, an Option::is_none_or() if it actually existed
-                            !world
-                                .get_block(world_neighbor_pos)
-                                .is_some_and(|block_data| block_data.is_some())
-                        };
+                    for (face_index, ((dx, dy, dz), geometry, normals, uvs)) in
+                        util::mesh::NEIGHBOR_DATA.into_iter().enumerate()
+                    {
+                        let direction = IVec3::new(dx, dy, dz);
 
-                        if add_face {
-                            let static_block_data = block_registry.get_block_data(block_type.id);
+                        if self.is_face_visible(
+                            neighborhood,
+                            offset,
+                            direction,
+                            block_type.id,
+                            block_registry,
+                        ) {
+                            let ao = self.get_face_ao(
+                                neighborhood,
+                                offset,
+                                direction,
+                                geometry,
+                                block_registry,
+                            );
 
-                            mesh_builder.add_face(
+                            chunk_geometry
+                                .mesh_builder(static_block_data.render_layer)
+                                .add_face(
+                                    geometry,
+                                    normals,
+                                    uvs,
+                                    offset.as_vec3(),
+                                    1.0,
+                                    static_block_data.faces[face_index],
+                                    block_registry.atlas_size,
+                                    ao,
+                                    neighborhood.get_light(offset + direction),
+                                );
+                            chunk_geometry.collision.add_quad(
                                 geometry,
-                                normals,
-                                uvs,
                                 offset.as_vec3(),
-                                1.0,
-                                static_block_data.textures.get_coords(face),
-                                block_registry.atlas_size,
+                                Vec3::ONE,
                             );
                         }
                     }
@@ -150,6 +444,262 @@ impl ChunkData {
             }
         }
 
-        mesh_builder.into_mesh()
+        chunk_geometry
+    }
+
+    /// Builds meshes where visible faces in the same plane with the same texture, ambient occlusion and render layer
+    /// are merged into rectangles. For every face direction, each slice of the chunk is turned into a 2D mask of
+    /// visible faces, which is then consumed greedily: a rectangle is grown along the u axis as far as possible, then
+    /// along the v axis for as long as every face in the next row matches. Blocks that aren't full cubes are added on
+    /// their own afterwards. Faces also need the same light to be merged.
+    fn get_mesh_greedy(
+        &self,
+        neighborhood: &ChunkNeighborhood,
+        block_registry: &BlockRegistry,
+    ) -> ChunkGeometry {
+        let mut chunk_geometry = ChunkGeometry::new();
+        let mut mask: Vec<Option<GreedyFaceKey>> = vec![None; CHUNK_SIZE * CHUNK_SIZE];
+
+        for (face_index, ((dx, dy, dz), geometry, normals, uvs)) in
+            util::mesh::NEIGHBOR_DATA.into_iter().enumerate()
+        {
+            let direction = IVec3::new(dx, dy, dz);
+
+            let normal_axis = util::mesh::normal_axis(direction);
+            let (u_axis, v_axis) = util::mesh::uv_axes(normal_axis);
+
+            let offset_at = |slice: usize, u: usize, v: usize| {
+                let mut offset = IVec3::ZERO;
+                offset[normal_axis] = slice as i32;
+                offset[u_axis] = u as i32;
+                offset[v_axis] = v as i32;
+
+                offset
+            };
+
+            for slice in 0..CHUNK_SIZE {
+                // Build the mask of visible faces in this slice
+                for v in 0..CHUNK_SIZE {
+                    for u in 0..CHUNK_SIZE {
+                        let offset = offset_at(slice, u, v);
+
+                        mask[v * CHUNK_SIZE + u] =
+                            self.get_from_raw_offset(offset).as_ref().and_then(|block| {
+                                let static_block_data = block_registry.get_block_data(block.id);
+
+                                (static_block_data.model.is_cube()
+                                    && self.is_face_visible(
+                                        neighborhood,
+                                        offset,
+                                        direction,
+                                        block.id,
+                                        block_registry,
+                                    ))
+                                .then(|| {
+                                    (
+                                        static_block_data.faces[face_index],
+                                        self.get_face_ao(
+                                            neighborhood,
+                                            offset,
+                                            direction,
+                                            geometry,
+                                            block_registry,
+                                        ),
+                                        neighborhood.get_light(offset + direction),
+                                        static_block_data.render_layer,
+                                    )
+                                })
+                            });
+                    }
+                }
+
+                // Merge the mask into rectangles
+                for v in 0..CHUNK_SIZE {
+                    let mut u = 0;
+
+                    while u < CHUNK_SIZE {
+                        let Some(face_key @ (texture, ao, light, render_layer)) =
+                            mask[v * CHUNK_SIZE + u]
+                        else {
+                            u += 1;
+                            continue;
+                        };
+
+                        let mut width = 1;
+                        while u + width < CHUNK_SIZE
+                            && mask[v * CHUNK_SIZE + u + width] == Some(face_key)
+                        {
+                            width += 1;
+                        }
+
+                        let mut height = 1;
+                        while v + height < CHUNK_SIZE
+                            && (u..u + width)
+                                .all(|u| mask[(v + height) * CHUNK_SIZE + u] == Some(face_key))
+                        {
+                            height += 1;
+                        }
+
+                        for row in v..v + height {
+                            mask[row * CHUNK_SIZE + u..row * CHUNK_SIZE + u + width].fill(None);
+                        }
+
+                        let mut size = Vec3::ONE;
+                        size[u_axis] = width as f32;
+                        size[v_axis] = height as f32;
+
+                        let offset = offset_at(slice, u, v).as_vec3();
+
+                        chunk_geometry.mesh_builder(render_layer).add_quad(
+                            geometry,
+                            normals,
+                            uvs,
+                            offset,
+                            size,
+                            Vec2::new(width as f32, height as f32),
+                            texture,
+                            block_registry.atlas_size,
+                            ao,
+                            light,
+                        );
+                        chunk_geometry.collision.add_quad(geometry, offset, size);
+
+                        u += width;
+                    }
+                }
+            }
+        }
+
+        for index in 0..CHUNK_VOLUME {
+            let Some(block) = self.blocks.get(index).as_ref() else {
+                continue;
+            };
+
+            if !block_registry.get_block_data(block.id).model.is_cube() {
+                self.add_block_model(
+                    neighborhood,
+                    Self::deindexify(index),
+                    block.id,
+                    block_registry,
+                    &mut chunk_geometry,
+                );
+            }
+        }
+
+        chunk_geometry
+    }
+}
+
+/// The geometry built from the blocks of a chunk.
+pub struct ChunkGeometry {
+    /// Faces of opaque and cutout blocks.
+    pub opaque: ChunkMeshBuilder,
+
+    /// Faces of translucent blocks, which are blended over everything behind them.
+    pub translucent: ChunkMeshBuilder,
+
+    pub collision: CollisionMeshBuilder,
+}
+
+impl ChunkGeometry {
+    fn new() -> Self {
+        Self {
+            opaque: ChunkMeshBuilder::new(),
+            translucent: ChunkMeshBuilder::new(),
+            collision: CollisionMeshBuilder::new(),
+        }
+    }
+
+    /// The mesh that faces of blocks in the render layer go into.
+    fn mesh_builder(&mut self, render_layer: RenderLayer) -> &mut ChunkMeshBuilder {
+        match render_layer {
+            RenderLayer::Opaque | RenderLayer::Cutout => &mut self.opaque,
+            RenderLayer::Translucent => &mut self.translucent,
+        }
+    }
+}
+
+/// Snapshots of a chunk and the 26 chunks around it, so the chunk can be meshed on another thread while the world
+/// keeps changing. The face neighbors are needed for face culling, the edge and corner neighbors for ambient
+/// occlusion. Snapshots are cheap, since chunks are shared and only copied when they are modified.
+pub struct ChunkNeighborhood {
+    chunk_pos: ChunkPos,
+    chunks: [Option<Arc<ChunkData>>; 27],
+}
+
+impl ChunkNeighborhood {
+    fn chunk_index(chunk_offset: IVec3) -> usize {
+        let chunk_offset = chunk_offset + 1;
+        (chunk_offset.z * 9 + chunk_offset.y * 3 + chunk_offset.x) as usize
+    }
+
+    /// Takes a snapshot of the chunk and its neighbors. Returns `None` if the chunk itself doesn't exist.
+    pub fn snapshot(world: &ExcavateManufacturateWorld, chunk_pos: ChunkPos) -> Option<Self> {
+        world.get_chunk_shared(chunk_pos)?;
+
+        let mut chunks: [Option<Arc<ChunkData>>; 27] = Default::default();
+
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    let chunk_offset = IVec3::new(x, y, z);
+
+                    chunks[Self::chunk_index(chunk_offset)] =
+                        world.get_chunk_shared(chunk_pos + ChunkPos::from(chunk_offset));
+                }
+            }
+        }
+
+        Some(Self { chunk_pos, chunks })
+    }
+
+    pub fn chunk_pos(&self) -> ChunkPos {
+        self.chunk_pos
+    }
+
+    /// The chunk that this neighborhood was taken around.
+    pub fn center(&self) -> &Arc<ChunkData> {
+        self.chunks[Self::chunk_index(IVec3::ZERO)]
+            .as_ref()
+            .expect("The center of a chunk neighborhood always exists")
+    }
+
+    /// Gets the block at an offset relative to the center chunk, which may reach up to one chunk outside of it.
+    /// Returns `None` if the chunk containing the block doesn't exist.
+    pub fn get_block(&self, offset: IVec3) -> Option<&BlockData> {
+        let chunk_offset = offset.div_euclid(IVec3::splat(CHUNK_SIZE as i32));
+
+        if chunk_offset.abs().max_element() > 1 {
+            return None;
+        }
+
+        self.chunks[Self::chunk_index(chunk_offset)]
+            .as_ref()
+            .map(|chunk| chunk.get(BlockPos::from(offset)))
+    }
+
+    /// Gets the sky light and block light at an offset relative to the center chunk, like
+    /// [`ChunkNeighborhood::get_block`]. Missing chunks aren't generated yet, so they get full sky light.
+    pub fn get_light(&self, offset: IVec3) -> [u8; 2] {
+        let chunk_offset = offset.div_euclid(IVec3::splat(CHUNK_SIZE as i32));
+
+        let chunk = (chunk_offset.abs().max_element() <= 1)
+            .then(|| self.chunks[Self::chunk_index(chunk_offset)].as_ref())
+            .flatten();
+
+        match chunk {
+            Some(chunk) => {
+                LightChannel::ALL.map(|channel| chunk.get_light(BlockPos::from(offset), channel))
+            }
+            None => [MAX_LIGHT_LEVEL, 0],
+        }
+    }
+
+    pub fn get_mesh(
+        &self,
+        block_registry: &BlockRegistry,
+        meshing_mode: MeshingMode,
+    ) -> ChunkGeometry {
+        self.center().get_mesh(self, block_registry, meshing_mode)
     }
 }
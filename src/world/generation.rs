@@ -1,10 +1,10 @@
-use std::{ops::Deref, sync::Arc};
+use std::{cell::OnceCell, ops::Deref, sync::Arc};
 
 use crate::world::worldgen::WorldGenerator;
 use bevy::{
     prelude::*,
     tasks::{AsyncComputeTaskPool, ComputeTaskPool, Task, TaskPool},
-    utils::HashSet,
+    utils::{HashMap, HashSet},
 };
 
 use crate::{
@@ -13,29 +13,59 @@ use crate::{
 };
 
 use super::{
-    block::registry::BlockRegistryResource,
+    block::{
+        registry::{BlockRegistry, BlockRegistryResource},
+        BlockData,
+    },
     chunk::ChunkData,
-    render::{ChunkSpawnQueue, SpawnedChunks},
+    heightmap, light,
+    proto_chunk::{self, GenerationStage, ProtoChunk},
+    render::{ChunkSpawnQueue, PossiblySpawnedChunks, SpawnedChunks},
     render_distance::RenderDistance,
-    worldgen::{ActiveWorldGenerator, OverworldGenerator},
+    save::{ActiveWorldSave, WorldSave},
+    worldgen::{
+        feature::{self, FeatureBlocks},
+        ActiveWorldGenerator, OverworldGenerator, WorldGenFeatures,
+    },
+    CHUNK_SIZE_INT,
 };
 use crate::world::world_access::ExcavateManufacturateWorld;
 
+/// Every chunk that started generating, and how far it got. Complete chunks stay in here, so they aren't generated
+/// again.
 #[derive(Resource, Deref, DerefMut)]
-pub struct PossiblyGeneratedChunks(HashSet<ChunkPos>);
+pub struct ProtoChunks(HashMap<ChunkPos, ProtoChunk>);
+
+impl ProtoChunks {
+    fn stage(&self, chunk_pos: ChunkPos) -> Option<GenerationStage> {
+        self.get(&chunk_pos).and_then(ProtoChunk::stage)
+    }
+}
 
 pub fn setup(mut commands: Commands) {
-    commands.insert_resource(PossiblyGeneratedChunks(HashSet::new()));
+    commands.insert_resource(ProtoChunks(HashMap::new()));
     info!("Setup chunk generator");
 }
 
 pub fn cleanup(mut commands: Commands) {
-    commands.remove_resource::<PossiblyGeneratedChunks>();
+    commands.remove_resource::<ProtoChunks>();
     info!("Cleaned up chunk generator");
 }
 
+/// What a task produced by running a stage of a chunk.
+struct StageOutput {
+    chunk_pos: ChunkPos,
+
+    /// The stage the chunk finished, which skips ahead to the last one for chunks that were loaded from disk.
+    stage: GenerationStage,
+    chunk_data: ChunkData,
+
+    /// The blocks the chunk's features placed into other chunks.
+    feature_blocks: FeatureBlocks,
+}
+
 #[derive(Component)]
-pub struct GeneratedChunkTask(Task<(ChunkPos, ChunkData)>);
+pub struct GeneratedChunkTask(Task<StageOutput>);
 
 // So we can make which task pool you use be generic
 pub trait GetTaskPool: Deref<Target = TaskPool> + 'static {
@@ -55,102 +85,343 @@ impl GetTaskPool for ComputeTaskPool {
         ComputeTaskPool::get()
     }
 }
+
+/// Everything the stages of generation need, shared with the tasks that run them.
+#[derive(Clone)]
+struct StageContext {
+    world_generator: Arc<OverworldGenerator>,
+    world_gen_features: WorldGenFeatures,
+    world_save: Arc<WorldSave>,
+    block_registry: Arc<BlockRegistry>,
+}
+
+impl StageContext {
+    /// Runs a stage of a chunk. Every stage but the first continues from the blocks of the stage before.
+    fn run_stage(
+        &self,
+        chunk_pos: ChunkPos,
+        stage: GenerationStage,
+        chunk_data: Option<ChunkData>,
+    ) -> StageOutput {
+        // Chunks that were saved before are loaded from disk, and don't need any of the other stages
+        if stage == GenerationStage::Noise {
+            if let Some(chunk_data) = self.world_save.load_chunk(chunk_pos) {
+                return self.load_chunk(chunk_pos, chunk_data);
+            }
+        }
+
+        let (chunk_data, feature_blocks) = match (stage, chunk_data) {
+            (GenerationStage::Lighting, Some(mut chunk_data)) => {
+                self.light(&mut chunk_data);
+                (chunk_data, FeatureBlocks::new())
+            }
+            (stage, chunk_data) => self.generate(chunk_pos, stage, chunk_data),
+        };
+
+        StageOutput {
+            chunk_pos,
+            stage,
+            chunk_data,
+            feature_blocks,
+        }
+    }
+
+    /// Loads a chunk that was saved before. Its features reach into neighbors that may not have been saved, so the
+    /// chunk is generated anew to place them again. They only depend on the seed and the terrain, so they come out the
+    /// same as when the chunk was first generated.
+    fn load_chunk(&self, chunk_pos: ChunkPos, mut chunk_data: ChunkData) -> StageOutput {
+        let mut generated = None;
+        let mut feature_blocks = FeatureBlocks::new();
+
+        for stage in GenerationStage::ALL {
+            if stage < GenerationStage::Lighting {
+                let (stage_data, stage_feature_blocks) = self.generate(chunk_pos, stage, generated);
+                generated = Some(stage_data);
+                feature_blocks = stage_feature_blocks;
+            }
+        }
+
+        self.light(&mut chunk_data);
+
+        StageOutput {
+            chunk_pos,
+            stage: GenerationStage::Lighting,
+            chunk_data,
+            feature_blocks,
+        }
+    }
+
+    /// Runs a stage that generates blocks. Returns them together with the blocks the chunk's features placed into
+    /// other chunks.
+    fn generate(
+        &self,
+        chunk_pos: ChunkPos,
+        stage: GenerationStage,
+        chunk_data: Option<ChunkData>,
+    ) -> (ChunkData, FeatureBlocks) {
+        let world_generator = &self.world_generator;
+        let block_registry = &self.block_registry;
+        let origin = BlockPos::from(chunk_pos);
+
+        let mut feature_blocks = FeatureBlocks::new();
+
+        let chunk_data = match (stage, chunk_data) {
+            (GenerationStage::Noise, _) => world_generator.terrain_noise(chunk_pos, block_registry),
+            (GenerationStage::Surface, Some(mut chunk_data)) => {
+                world_generator.build_surface(chunk_pos, &mut chunk_data, block_registry);
+                chunk_data
+            }
+            (GenerationStage::Carvers, Some(chunk_data)) => {
+                let carved = world_generator.carve(chunk_pos);
+
+                // The ground below the bottom layer lies in the chunk below, which is carved the same way every time
+                let carved_below = OnceCell::new();
+                let is_carved = |offset: IVec3| {
+                    if offset.y < 0 {
+                        carved_below
+                            .get_or_init(|| {
+                                world_generator.carve(chunk_pos - ChunkPos::new(0, 1, 0))
+                            })
+                            .contains(offset + IVec3::new(0, CHUNK_SIZE_INT, 0))
+                    } else {
+                        carved.contains(offset)
+                    }
+                };
+
+                ChunkData::with_data(|offset| {
+                    let terrain = chunk_data.get_from_raw_offset(offset.inner());
+
+                    // Plants don't float above caves that carved out the ground they grow on
+                    let ground_carved = terrain.is_some()
+                        && !heightmap::is_solid(terrain, block_registry)
+                        && is_carved(offset.inner() - IVec3::Y);
+
+                    if carved.contains(offset.inner()) || ground_carved {
+                        BlockData::none()
+                    } else {
+                        terrain.clone()
+                    }
+                })
+            }
+            (GenerationStage::Features, Some(chunk_data)) => {
+                let seed = self.world_save.seed();
+
+                let mut chunk_data = ChunkData::with_data(|offset| {
+                    let terrain = chunk_data.get_from_raw_offset(offset.inner()).clone();
+                    self.world_gen_features
+                        .generate(offset + origin, terrain, seed, block_registry)
+                });
+
+                world_generator.populate(chunk_pos, &mut chunk_data, block_registry);
+
+                feature_blocks =
+                    world_generator.place_features(chunk_pos, &chunk_data, block_registry);
+                feature::place_in_chunk(
+                    &mut chunk_data,
+                    feature_blocks.take_chunk(chunk_pos),
+                    block_registry,
+                );
+
+                chunk_data
+            }
+            (GenerationStage::Lighting, _) => unreachable!("Lighting doesn't generate blocks"),
+            (stage, None) => unreachable!("{:?} needs the blocks of the stage before", stage),
+        };
+
+        (chunk_data, feature_blocks)
+    }
+
+    /// Lights a complete chunk and computes its heightmap.
+    fn light(&self, chunk_data: &mut ChunkData) {
+        light::light_chunk(chunk_data, &self.block_registry);
+
+        if !chunk_data.is_empty() {
+            heightmap::compute_heightmap(chunk_data, &self.block_registry);
+        }
+    }
+}
+
+/// The last stage a chunk needs to reach, by its distance to the player in chunks. Chunks within the render distance
+/// are generated completely, and every ring of chunks around them one stage less, since a chunk can only advance once
+/// its neighbors finished the stage before.
+fn target_stage(distance: i32, render_distance: i32) -> Option<GenerationStage> {
+    let stages_short = (distance - render_distance).max(0) as usize;
+
+    GenerationStage::ALL
+        .len()
+        .checked_sub(stages_short + 1)
+        .map(|index| GenerationStage::ALL[index])
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn generate_chunks_multithreaded<T: GetTaskPool>(
     mut commands: Commands,
-    em_world: Res<ExcavateManufacturateWorld>,
     world_generator: Res<ActiveWorldGenerator<OverworldGenerator>>,
+    world_gen_features: Res<WorldGenFeatures>,
+    world_save: Res<ActiveWorldSave>,
     block_registry: Res<BlockRegistryResource>,
     render_distance: Res<RenderDistance>,
     player_query: Query<&ChunkPos, With<Player>>,
-    mut possibly_generated_chunks: ResMut<PossiblyGeneratedChunks>,
+    mut proto_chunks: ResMut<ProtoChunks>,
 ) {
     let player_chunk_pos = *player_query.single();
 
-    let lower = -render_distance.chunks();
-    let upper = render_distance.chunks();
+    // The outermost rings only get through the first stages
+    let reach = render_distance.chunks() + GenerationStage::ALL.len() as i32 - 1;
 
     let thread_pool = T::get();
 
-    let mut chunk_positions = Vec::new();
+    let mut chunks_to_advance = Vec::new();
 
-    for x_offset in lower..=upper {
-        for y_offset in lower..=upper {
-            for z_offset in lower..=upper {
+    for x_offset in -reach..=reach {
+        for y_offset in -reach..=reach {
+            for z_offset in -reach..=reach {
                 let chunk_pos = player_chunk_pos + ChunkPos::new(x_offset, y_offset, z_offset);
+                let distance = x_offset.abs().max(y_offset.abs()).max(z_offset.abs());
+
+                let next_stage = match proto_chunks.get(&chunk_pos) {
+                    Some(proto_chunk) if proto_chunk.is_in_progress() => continue,
+                    Some(proto_chunk) => proto_chunk.next_stage(),
+                    None => Some(GenerationStage::Noise),
+                };
+
+                let Some(next_stage) = next_stage else {
+                    continue; // The chunk is complete
+                };
 
-                if possibly_generated_chunks.contains(&chunk_pos)
-                    || em_world.chunk_exists(chunk_pos)
-                {
+                if target_stage(distance, render_distance.chunks()) < Some(next_stage) {
                     continue;
                 }
 
-                chunk_positions.push(chunk_pos);
+                let neighbors_ready = match next_stage.previous() {
+                    Some(previous_stage) => proto_chunk::neighbors(chunk_pos)
+                        .all(|neighbor| proto_chunks.stage(neighbor) >= Some(previous_stage)),
+                    None => true,
+                };
+
+                if neighbors_ready {
+                    chunks_to_advance.push((chunk_pos, next_stage));
+                }
             }
         }
     }
 
-    chunk_positions.sort_unstable_by(|&a, &b| {
+    chunks_to_advance.sort_unstable_by(|&(a, _), &(b, _)| {
         player_chunk_pos
             .distance_squared(a.inner())
             .cmp(&player_chunk_pos.distance_squared(b.inner()))
     });
 
-    for chunk_pos in chunk_positions {
-        let world_generator = Arc::clone(&world_generator);
-        let block_registry = Arc::clone(&block_registry);
+    let context = StageContext {
+        world_generator: Arc::clone(&world_generator),
+        world_gen_features: world_gen_features.clone(),
+        world_save: Arc::clone(&world_save),
+        block_registry: Arc::clone(&block_registry),
+    };
 
-        let task = thread_pool.spawn(async move {
-            let chunk_data = ChunkData::with_data(|block_pos| {
-                let block_pos = block_pos + BlockPos::from(chunk_pos);
-                world_generator.terrain_noise(block_pos, &block_registry)
-            });
+    for (chunk_pos, stage) in chunks_to_advance {
+        let proto_chunk = proto_chunks
+            .entry(chunk_pos)
+            .or_insert_with(ProtoChunk::new);
+        let mut chunk_data = proto_chunk.start_stage();
 
-            (chunk_pos, chunk_data)
-        });
+        // Every neighbor has placed its features by now, so the blocks they placed into this chunk are all there
+        if stage == GenerationStage::Lighting {
+            if let Some(chunk_data) = chunk_data.as_mut() {
+                feature::place_in_chunk(
+                    chunk_data,
+                    proto_chunk.take_feature_blocks(),
+                    &block_registry,
+                );
+            }
+        }
+
+        let context = context.clone();
+        let task =
+            thread_pool.spawn(async move { context.run_stage(chunk_pos, stage, chunk_data) });
 
         commands.spawn(GeneratedChunkTask(task));
-        possibly_generated_chunks.insert(chunk_pos); // mark this chunk as being generated
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn poll_generated_chunks(
     mut commands: Commands,
     mut tasks: Query<(Entity, &mut GeneratedChunkTask)>,
     mut em_world: ResMut<ExcavateManufacturateWorld>,
+    mut proto_chunks: ResMut<ProtoChunks>,
     spawned_chunks: Res<SpawnedChunks>,
+    possibly_spawned_chunks: Res<PossiblySpawnedChunks>,
     spawn_queue: Res<ChunkSpawnQueue>,
 ) {
     for (entity, mut task) in tasks.iter_mut() {
-        if let Some((chunk_pos, chunk_data)) =
-            bevy::tasks::block_on(futures_lite::future::poll_once(&mut task.0))
-        {
-            if chunk_data.is_empty() {
-                // Don't bother doing operations for an empty chunk, end this task.
-                drop(chunk_data);
-                commands.entity(entity).despawn();
-
-                continue;
-            }
+        let Some(output) = bevy::tasks::block_on(futures_lite::future::poll_once(&mut task.0))
+        else {
+            continue;
+        };
+
+        commands.entity(entity).despawn();
+
+        let StageOutput {
+            chunk_pos,
+            stage,
+            chunk_data,
+            feature_blocks,
+        } = output;
+
+        let mut chunk_positions_to_rebuild = HashSet::new();
+
+        let proto_chunk = proto_chunks
+            .get_mut(&chunk_pos)
+            .expect("Chunks are only generated through their proto chunk");
+
+        if stage == GenerationStage::Lighting {
+            proto_chunk.finish_stage(stage, None);
+
+            // Every neighbor placed its features before the chunk was lit, and chunks loaded from disk were saved
+            // with the blocks of their neighbors' features, so blocks that arrived in the meantime are already there
+            proto_chunk.take_feature_blocks();
+
+            let is_empty = chunk_data.is_empty();
 
+            // Empty chunks are kept as well, light spreads through them
             em_world.insert_chunk(chunk_pos, chunk_data);
-            commands.entity(entity).despawn();
-
-            let chunk_positions_to_rebuild = [
-                chunk_pos,
-                chunk_pos + ChunkPos::new(1, 0, 0),
-                chunk_pos - ChunkPos::new(1, 0, 0),
-                chunk_pos + ChunkPos::new(0, 1, 0),
-                chunk_pos - ChunkPos::new(0, 1, 0),
-                chunk_pos + ChunkPos::new(0, 0, 1),
-                chunk_pos - ChunkPos::new(0, 0, 1),
-            ];
-
-            // If the chunk was falsely spawned before it finished generating, rebuild it.
-            // Also, rebuild its neighbors, because their own mesh will need to be updated based on the newly generated chunk.
-            for chunk_pos in chunk_positions_to_rebuild {
-                if spawned_chunks.contains_key(&chunk_pos) {
-                    spawn_queue.push(chunk_pos);
-                }
+
+            // Nothing to mesh in an empty chunk, and the neighbors have treated it as empty all along
+            if !is_empty {
+                // If the chunk was falsely spawned before it finished generating, rebuild it.
+                // Also, rebuild its neighbors, because their own mesh will need to be updated based on the newly generated chunk.
+                chunk_positions_to_rebuild.extend([
+                    chunk_pos,
+                    chunk_pos + ChunkPos::new(1, 0, 0),
+                    chunk_pos - ChunkPos::new(1, 0, 0),
+                    chunk_pos + ChunkPos::new(0, 1, 0),
+                    chunk_pos - ChunkPos::new(0, 1, 0),
+                    chunk_pos + ChunkPos::new(0, 0, 1),
+                    chunk_pos - ChunkPos::new(0, 0, 1),
+                ]);
+            }
+        } else {
+            proto_chunk.finish_stage(stage, Some(chunk_data));
+        }
+
+        // Complete chunks already have the blocks, see above. The others get them right before they're lit
+        for (target_chunk_pos, blocks) in feature_blocks.into_chunks() {
+            let target = proto_chunks
+                .entry(target_chunk_pos)
+                .or_insert_with(ProtoChunk::new);
+
+            if !target.is_complete() {
+                target.receive_feature_blocks(blocks);
+            }
+        }
+
+        // Chunks that are still queued or being meshed may have been snapshotted before this chunk existed
+        for chunk_pos in chunk_positions_to_rebuild {
+            if spawned_chunks.contains_key(&chunk_pos)
+                || possibly_spawned_chunks.contains(&chunk_pos)
+            {
+                spawn_queue.push(chunk_pos);
             }
         }
     }
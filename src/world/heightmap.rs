@@ -0,0 +1,171 @@
+use std::{collections::BTreeSet, sync::Arc};
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::util::{block_pos::BlockPos, chunk_pos::ChunkPos};
+
+use super::{
+    block::{registry::BlockRegistry, BlockData},
+    chunk::ChunkData,
+    CHUNK_SIZE, CHUNK_SIZE_INT,
+};
+
+/// Whether the block counts towards the surface, which is everything that can be stood on. Plants and other blocks
+/// without collision don't.
+pub fn is_solid(block_data: &BlockData, block_registry: &BlockRegistry) -> bool {
+    block_data.as_ref().is_some_and(|block| {
+        !block_registry
+            .get_block_data(block.id)
+            .model
+            .collision_boxes()
+            .is_empty()
+    })
+}
+
+fn column_index(x: i32, z: i32) -> usize {
+    z as usize * CHUNK_SIZE + x as usize
+}
+
+/// The highest solid block of every column of blocks in a chunk, as the offset from the bottom of the chunk.
+#[derive(Clone)]
+pub struct ChunkHeightmap {
+    /// `None` for columns without any solid block.
+    heights: Box<[Option<u8>]>,
+}
+
+impl ChunkHeightmap {
+    pub fn empty() -> Self {
+        Self {
+            heights: vec![None; CHUNK_SIZE * CHUNK_SIZE].into_boxed_slice(),
+        }
+    }
+
+    /// The height of the column at the x and z offsets inside of the chunk.
+    pub fn get(&self, x: i32, z: i32) -> Option<u8> {
+        self.heights[column_index(x, z)]
+    }
+
+    fn set(&mut self, x: i32, z: i32, height: Option<u8>) {
+        self.heights[column_index(x, z)] = height;
+    }
+}
+
+/// Finds the highest solid block of a column of the chunk by going down from the top.
+fn scan_column(
+    chunk_data: &ChunkData,
+    x: i32,
+    z: i32,
+    block_registry: &BlockRegistry,
+) -> Option<u8> {
+    (0..CHUNK_SIZE_INT)
+        .rev()
+        .find(|&y| {
+            is_solid(
+                chunk_data.get_from_raw_offset(IVec3::new(x, y, z)),
+                block_registry,
+            )
+        })
+        .map(|y| y as u8)
+}
+
+/// Fills the heightmap of a chunk from its blocks.
+pub fn compute_heightmap(chunk_data: &mut ChunkData, block_registry: &BlockRegistry) {
+    let mut heightmap = ChunkHeightmap::empty();
+
+    for x in 0..CHUNK_SIZE_INT {
+        for z in 0..CHUNK_SIZE_INT {
+            heightmap.set(x, z, scan_column(chunk_data, x, z, block_registry));
+        }
+    }
+
+    *chunk_data.heightmap_mut() = heightmap;
+}
+
+/// Updates the heightmap of a chunk after the block at the position changed.
+pub fn update_heightmap(
+    chunk_data: &mut ChunkData,
+    block_pos: BlockPos,
+    block_registry: &BlockRegistry,
+) {
+    let offset = block_pos.as_chunk_offset();
+    let height = chunk_data.heightmap().get(offset.x, offset.z);
+
+    let new_height = if is_solid(chunk_data.get(block_pos), block_registry) {
+        // The block can only have become the new top of the column
+        height.max(Some(offset.y as u8))
+    } else if height == Some(offset.y as u8) {
+        // The top of the column was removed, so the next solid block below takes its place
+        scan_column(chunk_data, offset.x, offset.z, block_registry)
+    } else {
+        height
+    };
+
+    chunk_data
+        .heightmap_mut()
+        .set(offset.x, offset.z, new_height);
+}
+
+/// The highest solid block of every column of blocks in a column of chunks, over all of its loaded chunks.
+pub struct ColumnHeightmap {
+    /// The y coordinates of the loaded chunks in this column.
+    chunk_ys: BTreeSet<i32>,
+
+    /// The y coordinate of the highest solid block of each column, `None` for columns without any.
+    heights: Box<[Option<i32>]>,
+}
+
+impl ColumnHeightmap {
+    pub fn new() -> Self {
+        Self {
+            chunk_ys: BTreeSet::new(),
+            heights: vec![None; CHUNK_SIZE * CHUNK_SIZE].into_boxed_slice(),
+        }
+    }
+
+    /// The height of the column at the x and z offsets inside of the chunk column.
+    pub fn get(&self, x: i32, z: i32) -> Option<i32> {
+        self.heights[column_index(x, z)]
+    }
+
+    /// Takes the heights of a chunk that was added to this column into account. A new chunk can only raise the
+    /// surface.
+    pub fn add_chunk(&mut self, chunk_y: i32, chunk_data: &ChunkData) {
+        self.chunk_ys.insert(chunk_y);
+
+        for x in 0..CHUNK_SIZE_INT {
+            for z in 0..CHUNK_SIZE_INT {
+                let chunk_height = chunk_data
+                    .heightmap()
+                    .get(x, z)
+                    .map(|height| chunk_y * CHUNK_SIZE_INT + height as i32);
+
+                let height = &mut self.heights[column_index(x, z)];
+                *height = (*height).max(chunk_height);
+            }
+        }
+    }
+
+    /// Finds the height of a single column again from the heightmaps of the chunks, after a block in it changed.
+    pub fn update_column(
+        &mut self,
+        column_pos: IVec2,
+        x: i32,
+        z: i32,
+        chunks: &HashMap<ChunkPos, Arc<ChunkData>>,
+    ) {
+        // The highest chunk with any solid block in the column holds the surface
+        self.heights[column_index(x, z)] = self.chunk_ys.iter().rev().find_map(|&chunk_y| {
+            let chunk_pos = ChunkPos::new(column_pos.x, chunk_y, column_pos.y);
+
+            chunks[&chunk_pos]
+                .heightmap()
+                .get(x, z)
+                .map(|height| chunk_y * CHUNK_SIZE_INT + height as i32)
+        });
+    }
+}
+
+/// The position of the column of chunks that the block is in, as the x and z coordinates of the chunks.
+pub fn column_pos(x: i32, z: i32) -> IVec2 {
+    IVec2::new(x, z).div_euclid(IVec2::splat(CHUNK_SIZE_INT))
+}
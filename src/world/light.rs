@@ -0,0 +1,626 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use crate::util::{block_pos::BlockPos, chunk_pos::ChunkPos};
+
+use super::{
+    block::{registry::BlockRegistry, BlockData},
+    chunk::{ChunkData, CHUNK_VOLUME},
+    CHUNK_SIZE_INT,
+};
+
+/// The brightest light level. Sky light under the open sky has this level, and so does the block light right at the
+/// brightest light sources.
+pub const MAX_LIGHT_LEVEL: u8 = 15;
+
+const DIRECTIONS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+
+/// Blocks are lit by two kinds of light that spread separately: light from the sky, and light that blocks emit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightChannel {
+    Sky,
+    Block,
+}
+
+impl LightChannel {
+    pub const ALL: [Self; 2] = [LightChannel::Sky, LightChannel::Block];
+
+    fn unpack(self, packed: u8) -> u8 {
+        match self {
+            LightChannel::Sky => packed >> 4,
+            LightChannel::Block => packed & 0x0f,
+        }
+    }
+
+    fn pack(self, packed: u8, level: u8) -> u8 {
+        match self {
+            LightChannel::Sky => (packed & 0x0f) | (level << 4),
+            LightChannel::Block => (packed & 0xf0) | level,
+        }
+    }
+
+    /// The level that light of this channel has after spreading one block in the direction. Light gets one level
+    /// darker with every block, except for full sky light, which shines straight down without getting darker.
+    fn spread(self, level: u8, direction: IVec3) -> u8 {
+        if self == LightChannel::Sky && level == MAX_LIGHT_LEVEL && direction == IVec3::NEG_Y {
+            MAX_LIGHT_LEVEL
+        } else {
+            level.saturating_sub(1)
+        }
+    }
+}
+
+/// The sky light and block light of every block in a chunk, four bits each. Chunks that are lit the same everywhere,
+/// like chunks of solid rock, store a single level for all of their blocks.
+#[derive(Clone)]
+pub struct ChunkLight {
+    /// Both light levels of every block, or `None` while every block has the levels in `uniform`.
+    levels: Option<Box<[u8]>>,
+    uniform: u8,
+}
+
+impl ChunkLight {
+    pub fn dark() -> Self {
+        Self {
+            levels: None,
+            uniform: 0,
+        }
+    }
+
+    pub fn get(&self, index: usize, channel: LightChannel) -> u8 {
+        let packed = self
+            .levels
+            .as_ref()
+            .map_or(self.uniform, |levels| levels[index]);
+
+        channel.unpack(packed)
+    }
+
+    pub fn set(&mut self, index: usize, channel: LightChannel, level: u8) {
+        if self.get(index, channel) == level {
+            return;
+        }
+
+        let uniform = self.uniform;
+        let levels = self
+            .levels
+            .get_or_insert_with(|| vec![uniform; CHUNK_VOLUME].into_boxed_slice());
+
+        levels[index] = channel.pack(levels[index], level);
+    }
+
+    /// Sets the light of the channel to the same level for every block.
+    pub fn fill(&mut self, channel: LightChannel, level: u8) {
+        match &mut self.levels {
+            Some(levels) => {
+                for packed in levels.iter_mut() {
+                    *packed = channel.pack(*packed, level);
+                }
+            }
+            None => self.uniform = channel.pack(self.uniform, level),
+        }
+    }
+}
+
+fn lets_light_through(block_data: &BlockData, block_registry: &BlockRegistry) -> bool {
+    match block_data.as_ref() {
+        Some(block) => block_registry.get_block_data(block.id).transparent,
+        None => true,
+    }
+}
+
+fn light_emission(block_data: &BlockData, block_registry: &BlockRegistry) -> u8 {
+    block_data.as_ref().map_or(0, |block| {
+        block_registry.get_block_data(block.id).light_emission
+    })
+}
+
+/// Lights a chunk on its own, as if it were open to the sky above and dark on every other side. Light is spread
+/// between the chunk and its neighbors once it's part of the world, see [`LightPropagation::connect_chunk`].
+pub fn light_chunk(chunk_data: &mut ChunkData, block_registry: &BlockRegistry) {
+    if chunk_data.is_empty() {
+        // Nothing blocks the sky and nothing emits light, so every block has the same light
+        chunk_data.fill_light(LightChannel::Sky, MAX_LIGHT_LEVEL);
+        return;
+    }
+
+    let is_in_chunk = |offset: IVec3| {
+        offset.cmpge(IVec3::ZERO).all() && offset.cmplt(IVec3::splat(CHUNK_SIZE_INT)).all()
+    };
+
+    for channel in LightChannel::ALL {
+        let mut queue = VecDeque::new();
+
+        match channel {
+            LightChannel::Sky => {
+                for x in 0..CHUNK_SIZE_INT {
+                    for z in 0..CHUNK_SIZE_INT {
+                        // Sky light shines down every column until something blocks it
+                        for y in (0..CHUNK_SIZE_INT).rev() {
+                            let offset = IVec3::new(x, y, z);
+
+                            if !lets_light_through(
+                                chunk_data.get_from_raw_offset(offset),
+                                block_registry,
+                            ) {
+                                break;
+                            }
+
+                            chunk_data.set_light(BlockPos::from(offset), channel, MAX_LIGHT_LEVEL);
+                            queue.push_back(offset);
+                        }
+                    }
+                }
+            }
+            LightChannel::Block => {
+                for index in 0..CHUNK_VOLUME {
+                    let offset = ChunkData::deindexify(index);
+                    let emission =
+                        light_emission(chunk_data.get_from_raw_offset(offset), block_registry);
+
+                    if emission > 0 {
+                        chunk_data.set_light(BlockPos::from(offset), channel, emission);
+                        queue.push_back(offset);
+                    }
+                }
+            }
+        }
+
+        while let Some(offset) = queue.pop_front() {
+            let level = chunk_data.get_light(BlockPos::from(offset), channel);
+
+            for direction in DIRECTIONS {
+                let neighbor = offset + direction;
+
+                if !is_in_chunk(neighbor)
+                    || !lets_light_through(chunk_data.get_from_raw_offset(neighbor), block_registry)
+                {
+                    continue;
+                }
+
+                let spread_level = channel.spread(level, direction);
+
+                if spread_level > chunk_data.get_light(BlockPos::from(neighbor), channel) {
+                    chunk_data.set_light(BlockPos::from(neighbor), channel, spread_level);
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+    }
+}
+
+/// Spreads light between the blocks of the loaded chunks, across chunk borders. Light only exists in loaded chunks,
+/// including chunks of nothing but air, so light can tell caves from the open sky. A missing chunk isn't generated
+/// yet, and the blocks below it are treated as open to the sky until it is.
+pub struct LightPropagation<'a> {
+    chunks: &'a mut HashMap<ChunkPos, Arc<ChunkData>>,
+    block_registry: &'a BlockRegistry,
+
+    /// Chunks whose meshes show light that changed.
+    changed_chunks: &'a mut HashSet<ChunkPos>,
+}
+
+impl<'a> LightPropagation<'a> {
+    pub fn new(
+        chunks: &'a mut HashMap<ChunkPos, Arc<ChunkData>>,
+        block_registry: &'a BlockRegistry,
+        changed_chunks: &'a mut HashSet<ChunkPos>,
+    ) -> Self {
+        Self {
+            chunks,
+            block_registry,
+            changed_chunks,
+        }
+    }
+
+    fn get_block(&self, block_pos: BlockPos) -> Option<&BlockData> {
+        self.chunks
+            .get(&ChunkPos::from(block_pos))
+            .map(|chunk_data| chunk_data.get(block_pos))
+    }
+
+    /// The light level at the position, or `None` if its chunk isn't loaded.
+    fn get_light(&self, block_pos: BlockPos, channel: LightChannel) -> Option<u8> {
+        self.chunks
+            .get(&ChunkPos::from(block_pos))
+            .map(|chunk_data| chunk_data.get_light(block_pos, channel))
+    }
+
+    fn set_light(&mut self, block_pos: BlockPos, channel: LightChannel, level: u8) {
+        let chunk_pos = ChunkPos::from(block_pos);
+
+        let Some(chunk_data) = self.chunks.get_mut(&chunk_pos) else {
+            return;
+        };
+
+        Arc::make_mut(chunk_data).set_light(block_pos, channel, level);
+
+        // Faces of the neighboring chunks that border the block show its light as well
+        if block_pos.is_on_chunk_border() {
+            self.changed_chunks
+                .extend(block_pos.get_touched_chunk_positions());
+        } else {
+            self.changed_chunks.insert(chunk_pos);
+        }
+    }
+
+    /// The light a block gets no matter its neighbors: the light it emits, or full sky light if nothing loaded is
+    /// above it.
+    fn source_level(&self, block_pos: BlockPos, channel: LightChannel) -> u8 {
+        let Some(block_data) = self.get_block(block_pos) else {
+            return 0;
+        };
+
+        match channel {
+            LightChannel::Sky => {
+                let above = block_pos + BlockPos::new(0, 1, 0);
+                let open_to_sky = self.get_block(above).is_none()
+                    && lets_light_through(block_data, self.block_registry);
+
+                if open_to_sky {
+                    MAX_LIGHT_LEVEL
+                } else {
+                    0
+                }
+            }
+            LightChannel::Block => light_emission(block_data, self.block_registry),
+        }
+    }
+
+    /// Spreads light outwards from the positions in the queue.
+    fn spread_light(&mut self, channel: LightChannel, mut queue: VecDeque<BlockPos>) {
+        while let Some(block_pos) = queue.pop_front() {
+            let Some(level) = self.get_light(block_pos, channel) else {
+                continue;
+            };
+
+            if level == 0 {
+                continue;
+            }
+
+            for direction in DIRECTIONS {
+                let neighbor = block_pos + BlockPos::from(direction);
+
+                let Some(neighbor_data) = self.get_block(neighbor) else {
+                    continue;
+                };
+
+                if !lets_light_through(neighbor_data, self.block_registry) {
+                    continue;
+                }
+
+                let spread_level = channel.spread(level, direction);
+
+                if self
+                    .get_light(neighbor, channel)
+                    .is_some_and(|neighbor_level| spread_level > neighbor_level)
+                {
+                    self.set_light(neighbor, channel, spread_level);
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+    }
+
+    /// Darkens everything that was lit by the positions in the queue, which have been darkened already and are
+    /// queued with the level they had before. Returns the positions that light has to be spread from again to fill
+    /// the darkened area: light sources inside of it, and brighter blocks around it.
+    fn remove_light(
+        &mut self,
+        channel: LightChannel,
+        mut queue: VecDeque<(BlockPos, u8)>,
+    ) -> VecDeque<BlockPos> {
+        let mut relight_queue = VecDeque::new();
+
+        while let Some((block_pos, previous_level)) = queue.pop_front() {
+            for direction in DIRECTIONS {
+                let neighbor = block_pos + BlockPos::from(direction);
+
+                let Some(neighbor_level) = self.get_light(neighbor, channel) else {
+                    continue;
+                };
+
+                if neighbor_level == 0 {
+                    continue;
+                }
+
+                if neighbor_level <= channel.spread(previous_level, direction) {
+                    // The neighbor may have been lit by this block
+                    self.set_light(neighbor, channel, 0);
+                    queue.push_back((neighbor, neighbor_level));
+
+                    let source_level = self.source_level(neighbor, channel);
+                    if source_level > 0 {
+                        self.set_light(neighbor, channel, source_level);
+                        relight_queue.push_back(neighbor);
+                    }
+                } else {
+                    // The neighbor is lit by something else
+                    relight_queue.push_back(neighbor);
+                }
+            }
+        }
+
+        relight_queue
+    }
+
+    /// Updates the light around a block that was just changed.
+    pub fn update_block(&mut self, block_pos: BlockPos) {
+        for channel in LightChannel::ALL {
+            let Some(previous_level) = self.get_light(block_pos, channel) else {
+                continue;
+            };
+
+            self.set_light(block_pos, channel, 0);
+            let mut relight_queue =
+                self.remove_light(channel, VecDeque::from([(block_pos, previous_level)]));
+
+            let source_level = self.source_level(block_pos, channel);
+            if source_level > 0 {
+                self.set_light(block_pos, channel, source_level);
+                relight_queue.push_back(block_pos);
+            }
+
+            // Light from the neighbors flows into the block if it lets light through
+            relight_queue.extend(
+                DIRECTIONS
+                    .into_iter()
+                    .map(|direction| block_pos + BlockPos::from(direction)),
+            );
+
+            self.spread_light(channel, relight_queue);
+        }
+    }
+
+    /// Spreads light between a chunk that was just added to the world and its neighbors. The chunk has to be lit on
+    /// its own with [`light_chunk`] before.
+    pub fn connect_chunk(&mut self, chunk_pos: ChunkPos) {
+        let origin = BlockPos::from(chunk_pos);
+        let above_exists = self
+            .chunks
+            .contains_key(&(chunk_pos + ChunkPos::new(0, 1, 0)));
+        let below_exists = self
+            .chunks
+            .contains_key(&(chunk_pos - ChunkPos::new(0, 1, 0)));
+
+        for channel in LightChannel::ALL {
+            let mut removal_queue = VecDeque::new();
+            let mut relight_queue = VecDeque::new();
+
+            if channel == LightChannel::Sky {
+                for x in 0..CHUNK_SIZE_INT {
+                    for z in 0..CHUNK_SIZE_INT {
+                        // The chunk was lit as if it were open to the sky, which it isn't if the chunk above blocks
+                        // it
+                        if above_exists {
+                            let top = origin + BlockPos::new(x, CHUNK_SIZE_INT - 1, z);
+                            self.remove_unlit_sky(top, &mut removal_queue);
+                        }
+
+                        // The chunk below was open to the sky while this chunk was missing
+                        if below_exists {
+                            let below = origin + BlockPos::new(x, -1, z);
+                            self.remove_unlit_sky(below, &mut removal_queue);
+                        }
+                    }
+                }
+            }
+
+            // Light spreads across every side of the chunk in both directions
+            for axis in 0..3 {
+                for (side, outwards) in [(0, -1), (CHUNK_SIZE_INT - 1, 1)] {
+                    for u in 0..CHUNK_SIZE_INT {
+                        for v in 0..CHUNK_SIZE_INT {
+                            let mut offset = IVec3::ZERO;
+                            offset[axis] = side;
+                            offset[(axis + 1) % 3] = u;
+                            offset[(axis + 2) % 3] = v;
+
+                            let mut outside = offset;
+                            outside[axis] += outwards;
+
+                            relight_queue.push_back(origin + BlockPos::from(offset));
+                            relight_queue.push_back(origin + BlockPos::from(outside));
+                        }
+                    }
+                }
+            }
+
+            relight_queue.extend(self.remove_light(channel, removal_queue));
+            self.spread_light(channel, relight_queue);
+        }
+
+        self.changed_chunks.insert(chunk_pos);
+    }
+
+    /// Darkens the block if it has full sky light without getting it from the block above, because it used to be
+    /// open to the sky.
+    fn remove_unlit_sky(
+        &mut self,
+        block_pos: BlockPos,
+        removal_queue: &mut VecDeque<(BlockPos, u8)>,
+    ) {
+        let above = block_pos + BlockPos::new(0, 1, 0);
+
+        let has_full_sky_light =
+            self.get_light(block_pos, LightChannel::Sky) == Some(MAX_LIGHT_LEVEL);
+        let lit_from_above = self
+            .get_light(above, LightChannel::Sky)
+            .unwrap_or(MAX_LIGHT_LEVEL)
+            == MAX_LIGHT_LEVEL;
+
+        if has_full_sky_light && !lit_from_above {
+            self.set_light(block_pos, LightChannel::Sky, 0);
+            removal_queue.push_back((block_pos, MAX_LIGHT_LEVEL));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::block::BlockName;
+
+    use super::*;
+
+    fn test_registry() -> BlockRegistry {
+        BlockRegistry::for_tests(&[
+            ("stone", "(textures: (top: \"stone\"), hardness: Hand)"),
+            (
+                "lamp",
+                "(textures: (top: \"lamp\"), hardness: Hand, light_emission: 15)",
+            ),
+        ])
+    }
+
+    fn block(block_registry: &BlockRegistry, name: &str) -> BlockData {
+        let block_name = BlockName(format!("test/{}", name).into());
+        BlockData::some(block_registry.create_block(&block_name).unwrap())
+    }
+
+    fn sky_light(chunk_data: &ChunkData, x: i32, y: i32, z: i32) -> u8 {
+        chunk_data.get_light(BlockPos::new(x, y, z), LightChannel::Sky)
+    }
+
+    #[test]
+    fn empty_chunks_are_open_to_the_sky() {
+        let block_registry = test_registry();
+
+        let mut chunk_data = ChunkData::empty();
+        light_chunk(&mut chunk_data, &block_registry);
+
+        for index in 0..CHUNK_VOLUME {
+            let block_pos = BlockPos::from(ChunkData::deindexify(index));
+            assert_eq!(
+                chunk_data.get_light(block_pos, LightChannel::Sky),
+                MAX_LIGHT_LEVEL
+            );
+            assert_eq!(chunk_data.get_light(block_pos, LightChannel::Block), 0);
+        }
+    }
+
+    #[test]
+    fn sky_light_shines_through_holes() {
+        let block_registry = test_registry();
+        let stone = block(&block_registry, "stone");
+
+        // A roof with a single hole in it
+        let mut chunk_data = ChunkData::with_data(|offset| {
+            if offset.y == 16 && !(offset.x == 10 && offset.z == 10) {
+                stone.clone()
+            } else {
+                BlockData::none()
+            }
+        });
+        light_chunk(&mut chunk_data, &block_registry);
+
+        assert_eq!(sky_light(&chunk_data, 0, 20, 0), MAX_LIGHT_LEVEL);
+        assert_eq!(sky_light(&chunk_data, 0, 16, 0), 0);
+
+        // Full sky light doesn't fade going straight down, but does going sideways
+        assert_eq!(sky_light(&chunk_data, 10, 0, 10), MAX_LIGHT_LEVEL);
+        assert_eq!(sky_light(&chunk_data, 11, 0, 10), MAX_LIGHT_LEVEL - 1);
+        assert_eq!(sky_light(&chunk_data, 15, 5, 10), MAX_LIGHT_LEVEL - 5);
+        assert_eq!(sky_light(&chunk_data, 30, 5, 30), 0);
+    }
+
+    #[test]
+    fn block_light_fades_with_distance() {
+        let block_registry = test_registry();
+        let lamp = block(&block_registry, "lamp");
+
+        let mut chunk_data = ChunkData::with_data(|offset| {
+            if offset.inner() == IVec3::splat(16) {
+                lamp.clone()
+            } else {
+                BlockData::none()
+            }
+        });
+        light_chunk(&mut chunk_data, &block_registry);
+
+        let block_light =
+            |x, y, z| chunk_data.get_light(BlockPos::new(x, y, z), LightChannel::Block);
+
+        assert_eq!(block_light(16, 16, 16), 15);
+        assert_eq!(block_light(17, 16, 16), 14);
+        assert_eq!(block_light(18, 17, 16), 12);
+        assert_eq!(block_light(16, 0, 16), 0);
+
+        // The lamp casts a shadow, but the light from around it fills it in
+        assert_eq!(sky_light(&chunk_data, 16, 15, 16), MAX_LIGHT_LEVEL - 1);
+    }
+
+    #[test]
+    fn light_spreads_between_chunks() {
+        let block_registry = test_registry();
+        let stone = block(&block_registry, "stone");
+
+        let below = ChunkPos::new(0, 0, 0);
+        let above = ChunkPos::new(0, 1, 0);
+
+        let mut chunks = HashMap::new();
+        let mut changed_chunks = HashSet::new();
+
+        // The chunk below is open to the sky while the chunk above is missing
+        let mut chunk_data = ChunkData::empty();
+        light_chunk(&mut chunk_data, &block_registry);
+        chunks.insert(below, Arc::new(chunk_data));
+        LightPropagation::new(&mut chunks, &block_registry, &mut changed_chunks)
+            .connect_chunk(below);
+
+        assert_eq!(
+            chunks[&below].get_light(BlockPos::new(5, 5, 5), LightChannel::Sky),
+            15
+        );
+
+        // A chunk with a stone floor covers it
+        let mut chunk_data = ChunkData::with_data(|offset| {
+            if offset.y == 0 {
+                stone.clone()
+            } else {
+                BlockData::none()
+            }
+        });
+        light_chunk(&mut chunk_data, &block_registry);
+        chunks.insert(above, Arc::new(chunk_data));
+        LightPropagation::new(&mut chunks, &block_registry, &mut changed_chunks)
+            .connect_chunk(above);
+
+        assert!(changed_chunks.contains(&below));
+        for block_pos in [
+            BlockPos::new(5, 5, 5),
+            BlockPos::new(0, CHUNK_SIZE_INT - 1, 0),
+        ] {
+            assert_eq!(chunks[&below].get_light(block_pos, LightChannel::Sky), 0);
+        }
+
+        // Breaking a block of the floor lets the sky in again
+        let hole = BlockPos::new(7, CHUNK_SIZE_INT, 7);
+        Arc::make_mut(chunks.get_mut(&above).unwrap()).set(hole, BlockData::none());
+        LightPropagation::new(&mut chunks, &block_registry, &mut changed_chunks).update_block(hole);
+
+        let light_below = |chunks: &HashMap<ChunkPos, Arc<ChunkData>>, x, y, z| {
+            chunks[&below].get_light(BlockPos::new(x, y, z), LightChannel::Sky)
+        };
+
+        assert_eq!(light_below(&chunks, 7, 0, 7), MAX_LIGHT_LEVEL);
+        assert_eq!(light_below(&chunks, 9, 10, 7), MAX_LIGHT_LEVEL - 2);
+
+        // And placing it again shuts it out
+        Arc::make_mut(chunks.get_mut(&above).unwrap()).set(hole, stone.clone());
+        LightPropagation::new(&mut chunks, &block_registry, &mut changed_chunks).update_block(hole);
+
+        assert_eq!(light_below(&chunks, 7, 0, 7), 0);
+        assert_eq!(light_below(&chunks, 9, 10, 7), 0);
+    }
+}
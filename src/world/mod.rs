@@ -4,7 +4,7 @@ use bevy::{
     tasks::AsyncComputeTaskPool,
 };
 
-use crate::state::MenuState;
+use crate::{state::MenuState, util::mesh::MeshingMode};
 
 use self::{
     collider::{ChunkColliderDisableEvent, ChunkColliderEnableEvent},
@@ -12,18 +12,27 @@ use self::{
 };
 
 pub mod block;
+pub mod block_id_table;
 pub mod chunk;
 pub mod collider;
 pub mod generation;
+pub mod heightmap;
+pub mod light;
+pub mod palette;
+pub mod proto_chunk;
+pub mod region;
 pub mod render;
 pub mod render_distance;
+pub mod save;
+pub mod seed;
 pub mod world_access;
 pub mod worldgen;
 
 pub const CHUNK_SIZE: usize = 32;
 pub const CHUNK_SIZE_INT: i32 = CHUNK_SIZE as i32;
 
-pub const NUM_CHUNKS_RENDERED_PER_FRAME: usize = 1;
+pub const NUM_CHUNKS_RENDERED_PER_FRAME: usize = 8;
+pub const NUM_CHUNKS_LIT_PER_FRAME: usize = 8;
 
 pub struct ExcavateManufacturateWorldPlugin;
 
@@ -33,13 +42,26 @@ impl Plugin for ExcavateManufacturateWorldPlugin {
             .add_event::<ChunkColliderDisableEvent>()
             .add_event::<BlockPlaceEvent>()
             .add_event::<BlockDestroyEvent>()
-            .add_systems(Startup, (render_distance::setup, block::registry::setup))
+            .init_resource::<MeshingMode>()
+            .add_systems(
+                Startup,
+                (
+                    render_distance::setup,
+                    save::init_settings,
+                    (
+                        crate::mods::setup,
+                        (block::registry::setup, worldgen::init_features),
+                        crate::mods::freeze,
+                    )
+                        .chain(),
+                ),
+            )
             .add_systems(
                 OnEnter(MenuState::InGame),
                 (
                     setup_light,
-                    world_access::setup,
-                    worldgen::setup,
+                    // The generator and the world are seeded from the save
+                    (save::setup, (worldgen::setup, world_access::setup)).chain(),
                     generation::setup,
                     render::setup,
                 ),
@@ -50,15 +72,28 @@ impl Plugin for ExcavateManufacturateWorldPlugin {
                     (
                         (
                             // Multithreaded chunk generation
-                            generation::poll_generated_chunks,
+                            (
+                                generation::poll_generated_chunks,
+                                world_access::connect_chunk_light,
+                            )
+                                .chain(),
                             generation::generate_chunks_multithreaded::<AsyncComputeTaskPool>,
                         ),
                         // generation::generate_chunks,
-                        render::populate_chunk_spawn_queue,
+                        (
+                            render::populate_chunk_spawn_queue,
+                            render::remesh_on_meshing_mode_change,
+                        ),
                         (
                             (
-                                render::spawn_chunks::<NUM_CHUNKS_RENDERED_PER_FRAME>,
+                                // Multithreaded chunk meshing
+                                render::poll_chunk_mesh_tasks,
+                                render::spawn_chunk_mesh_tasks::<
+                                    AsyncComputeTaskPool,
+                                    NUM_CHUNKS_RENDERED_PER_FRAME,
+                                >,
                                 render::despawn_chunks,
+                                render::sort_translucent_chunk_meshes,
                             ),
                             (
                                 collider::send_enable_chunk_colliders_near_mobs,
@@ -73,6 +108,8 @@ impl Plugin for ExcavateManufacturateWorldPlugin {
                         world_access::apply_block_place_events,
                         world_access::apply_block_destroy_events,
                     ),
+                    world_access::remesh_relit_chunks,
+                    save::autosave_world,
                 )
                     .chain()
                     .run_if(in_state(MenuState::InGame)),
@@ -80,8 +117,11 @@ impl Plugin for ExcavateManufacturateWorldPlugin {
             .add_systems(
                 OnExit(MenuState::InGame),
                 (
+                    // Save before the world data is removed
+                    save::save_world.before(world_access::cleanup),
                     remove_light,
                     world_access::cleanup,
+                    save::cleanup,
                     worldgen::cleanup,
                     generation::cleanup,
                     render::cleanup,
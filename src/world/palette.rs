@@ -0,0 +1,499 @@
+use std::io;
+
+use super::{block::BlockData, block_id_table::BlockIdTable};
+
+/// Palette indices packed into `u64` words. An index never spans two words, so some bits at the end of each word
+/// may go unused.
+#[derive(Clone)]
+struct PackedIndices {
+    bits: u32,
+    words: Vec<u64>,
+}
+
+impl PackedIndices {
+    fn new(bits: u32, len: usize) -> Self {
+        Self {
+            bits,
+            words: vec![0; Self::word_count(bits, len)],
+        }
+    }
+
+    fn indices_per_word(bits: u32) -> usize {
+        (u64::BITS / bits) as usize
+    }
+
+    fn word_count(bits: u32, len: usize) -> usize {
+        len.div_ceil(Self::indices_per_word(bits))
+    }
+
+    fn mask(&self) -> u64 {
+        (1 << self.bits) - 1
+    }
+
+    fn locate(&self, index: usize) -> (usize, u32) {
+        let indices_per_word = Self::indices_per_word(self.bits);
+
+        (
+            index / indices_per_word,
+            (index % indices_per_word) as u32 * self.bits,
+        )
+    }
+
+    fn get(&self, index: usize) -> usize {
+        let (word, shift) = self.locate(index);
+        ((self.words[word] >> shift) & self.mask()) as usize
+    }
+
+    fn set(&mut self, index: usize, value: usize) {
+        let (word, shift) = self.locate(index);
+        let mask = self.mask();
+
+        self.words[word] = (self.words[word] & !(mask << shift)) | ((value as u64 & mask) << shift);
+    }
+
+    /// Copies the indices into a new representation that uses the given amount of bits per index.
+    fn repacked(&self, bits: u32, len: usize) -> Self {
+        let mut repacked = Self::new(bits, len);
+
+        for index in 0..len {
+            repacked.set(index, self.get(index));
+        }
+
+        repacked
+    }
+}
+
+/// The smallest amount of bits needed to index into a palette of the given length.
+fn bits_for_palette_len(len: usize) -> u32 {
+    (usize::BITS - len.saturating_sub(1).leading_zeros()).max(1)
+}
+
+#[derive(Clone)]
+struct PaletteEntry {
+    block: BlockData,
+
+    /// How many positions currently point to this entry. Entries with a count of 0 are reused for new blocks.
+    count: u32,
+}
+
+#[derive(Clone)]
+enum Repr {
+    /// Every position holds the same block, no per-position data is stored.
+    Uniform(BlockData),
+    Paletted {
+        palette: Vec<PaletteEntry>,
+        indices: PackedIndices,
+    },
+}
+
+/// A fixed-length array of [`BlockData`], stored as a palette of distinct blocks plus a bit-packed index into that
+/// palette for every position. Arrays where every position holds the same block don't store any indices at all.
+#[derive(Clone)]
+pub struct PalettedBlocks {
+    len: usize,
+    repr: Repr,
+}
+
+impl PalettedBlocks {
+    pub fn uniform(len: usize, block: BlockData) -> Self {
+        Self {
+            len,
+            repr: Repr::Uniform(block),
+        }
+    }
+
+    pub fn from_fn<F>(len: usize, mut supplier: F) -> Self
+    where
+        F: FnMut(usize) -> BlockData,
+    {
+        let mut palette: Vec<PaletteEntry> = Vec::new();
+        let mut raw_indices = Vec::with_capacity(len);
+
+        // Neighboring positions are usually the same block, so check the last used entry first.
+        let mut last_index = 0;
+
+        for i in 0..len {
+            let block = supplier(i);
+
+            let palette_index = if palette
+                .get(last_index)
+                .is_some_and(|entry| entry.block == block)
+            {
+                last_index
+            } else if let Some(palette_index) =
+                palette.iter().position(|entry| entry.block == block)
+            {
+                palette_index
+            } else {
+                palette.push(PaletteEntry { block, count: 0 });
+                palette.len() - 1
+            };
+
+            palette[palette_index].count += 1;
+            raw_indices.push(palette_index);
+            last_index = palette_index;
+        }
+
+        if palette.len() == 1 {
+            return Self::uniform(len, palette.pop().unwrap().block);
+        }
+
+        let mut indices = PackedIndices::new(bits_for_palette_len(palette.len()), len);
+
+        for (i, palette_index) in raw_indices.into_iter().enumerate() {
+            indices.set(i, palette_index);
+        }
+
+        Self {
+            len,
+            repr: Repr::Paletted { palette, indices },
+        }
+    }
+
+    pub fn get(&self, index: usize) -> &BlockData {
+        assert!(index < self.len, "Index {} out of bounds", index);
+
+        match &self.repr {
+            Repr::Uniform(block) => block,
+            Repr::Paletted { palette, indices } => &palette[indices.get(index)].block,
+        }
+    }
+
+    /// Sets the block at the index, returning the block that was there before.
+    pub fn set(&mut self, index: usize, block: BlockData) -> BlockData {
+        assert!(index < self.len, "Index {} out of bounds", index);
+
+        if let Repr::Uniform(uniform_block) = &mut self.repr {
+            if *uniform_block == block {
+                return block;
+            }
+
+            // Switch to a palette with the uniform block as its only entry, all indices being 0 already point to it.
+            let uniform_block = std::mem::replace(uniform_block, BlockData::none());
+
+            self.repr = Repr::Paletted {
+                palette: vec![PaletteEntry {
+                    block: uniform_block,
+                    count: self.len as u32,
+                }],
+                indices: PackedIndices::new(1, self.len),
+            };
+        }
+
+        let Repr::Paletted { palette, indices } = &mut self.repr else {
+            unreachable!()
+        };
+
+        let old_palette_index = indices.get(index);
+
+        if palette[old_palette_index].block == block {
+            return block;
+        }
+
+        palette[old_palette_index].count -= 1;
+        let previous_block = palette[old_palette_index].block.clone();
+
+        let new_palette_index =
+            if let Some(palette_index) = palette.iter().position(|entry| entry.block == block) {
+                palette_index
+            } else if let Some(palette_index) = palette.iter().position(|entry| entry.count == 0) {
+                palette[palette_index].block = block;
+                palette_index
+            } else {
+                palette.push(PaletteEntry { block, count: 0 });
+
+                let required_bits = bits_for_palette_len(palette.len());
+                if required_bits > indices.bits {
+                    *indices = indices.repacked(required_bits, self.len);
+                }
+
+                palette.len() - 1
+            };
+
+        palette[new_palette_index].count += 1;
+        indices.set(index, new_palette_index);
+
+        if palette[new_palette_index].count == self.len as u32 {
+            // Every position holds the new block now
+            let block = palette.swap_remove(new_palette_index).block;
+            self.repr = Repr::Uniform(block);
+        }
+
+        previous_block
+    }
+
+    /// Counts the positions whose block matches the predicate.
+    pub fn count<F>(&self, mut predicate: F) -> u32
+    where
+        F: FnMut(&BlockData) -> bool,
+    {
+        match &self.repr {
+            Repr::Uniform(block) => {
+                if predicate(block) {
+                    self.len as u32
+                } else {
+                    0
+                }
+            }
+            Repr::Paletted { palette, .. } => palette
+                .iter()
+                .filter(|entry| predicate(&entry.block))
+                .map(|entry| entry.count)
+                .sum(),
+        }
+    }
+
+    /// Encodes the palette as the save's block IDs followed by the packed indices. Dynamic block data isn't part of
+    /// the encoding.
+    pub fn encode(&self, bytes: &mut Vec<u8>, block_ids: &BlockIdTable) {
+        match &self.repr {
+            Repr::Uniform(block) => {
+                bytes.push(0);
+                bytes.extend_from_slice(&block_ids.encode_block(block).to_le_bytes());
+            }
+            Repr::Paletted { palette, indices } => {
+                bytes.push(1);
+                bytes.extend_from_slice(&(palette.len() as u16).to_le_bytes());
+
+                for entry in palette.iter() {
+                    bytes.extend_from_slice(&block_ids.encode_block(&entry.block).to_le_bytes());
+                }
+
+                bytes.push(indices.bits as u8);
+
+                for word in indices.words.iter() {
+                    bytes.extend_from_slice(&word.to_le_bytes());
+                }
+            }
+        }
+    }
+
+    /// Decodes an array of the given length that was encoded with [`PalettedBlocks::encode`].
+    pub fn decode(len: usize, bytes: &[u8], block_ids: &BlockIdTable) -> io::Result<Self> {
+        let mut reader = ByteReader(bytes);
+
+        let decode_block = |save_id: u16| {
+            block_ids
+                .decode_block(save_id)
+                .ok_or_else(|| invalid_data(format!("Unknown block id {}", save_id)))
+        };
+
+        let blocks = match reader.u8()? {
+            0 => Self::uniform(len, decode_block(reader.u16()?)?),
+            1 => {
+                let palette_len = reader.u16()? as usize;
+                let palette = (0..palette_len)
+                    .map(|_| decode_block(reader.u16()?))
+                    .collect::<io::Result<Vec<_>>>()?;
+
+                let bits = reader.u8()? as u32;
+                if bits == 0 || bits > 16 || (1 << bits) < palette_len {
+                    return Err(invalid_data(format!(
+                        "Invalid index size of {} bits for a palette of {} blocks",
+                        bits, palette_len
+                    )));
+                }
+
+                let words = (0..PackedIndices::word_count(bits, len))
+                    .map(|_| reader.u64())
+                    .collect::<io::Result<Vec<_>>>()?;
+                let indices = PackedIndices { bits, words };
+
+                // Rebuild the palette rather than trusting the stored one, this also merges duplicate entries
+                let mut out_of_range = false;
+                let blocks = Self::from_fn(len, |i| {
+                    palette.get(indices.get(i)).cloned().unwrap_or_else(|| {
+                        out_of_range = true;
+                        BlockData::none()
+                    })
+                });
+
+                if out_of_range {
+                    return Err(invalid_data("Palette index out of range"));
+                }
+
+                blocks
+            }
+            tag => return Err(invalid_data(format!("Unknown storage tag {}", tag))),
+        };
+
+        if !reader.0.is_empty() {
+            return Err(invalid_data(format!(
+                "{} trailing bytes after block data",
+                reader.0.len()
+            )));
+        }
+
+        Ok(blocks)
+    }
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+struct ByteReader<'a>(&'a [u8]);
+
+impl ByteReader<'_> {
+    fn take<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        if self.0.len() < N {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
+
+        let (bytes, rest) = self.0.split_at(N);
+        self.0 = rest;
+
+        Ok(bytes.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        self.take::<1>().map(|bytes| bytes[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        self.take().map(u16::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        self.take().map(u64::from_le_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::block::{registry::BlockRegistry, BlockName};
+
+    use super::*;
+
+    const LEN: usize = 100;
+
+    /// A registry with a few distinct blocks, and those blocks.
+    fn test_blocks() -> (BlockRegistry, Vec<BlockData>) {
+        let names = ["a", "b", "c", "d", "e"];
+        let definitions: Vec<_> = names
+            .iter()
+            .map(|&name| (name, "(textures: (top: \"a\"), hardness: Hand)"))
+            .collect();
+        let block_registry = BlockRegistry::for_tests(&definitions);
+
+        let blocks = names
+            .iter()
+            .map(|name| {
+                let block_name = BlockName(format!("test/{}", name).into());
+                BlockData::some(block_registry.create_block(&block_name).unwrap())
+            })
+            .collect();
+
+        (block_registry, blocks)
+    }
+
+    fn is_uniform(blocks: &PalettedBlocks) -> bool {
+        matches!(blocks.repr, Repr::Uniform(_))
+    }
+
+    #[test]
+    fn bits_fit_the_palette() {
+        assert_eq!(bits_for_palette_len(1), 1);
+        assert_eq!(bits_for_palette_len(2), 1);
+        assert_eq!(bits_for_palette_len(3), 2);
+        assert_eq!(bits_for_palette_len(4), 2);
+        assert_eq!(bits_for_palette_len(5), 3);
+        assert_eq!(bits_for_palette_len(256), 8);
+        assert_eq!(bits_for_palette_len(257), 9);
+    }
+
+    #[test]
+    fn packed_indices_dont_overlap() {
+        // 64 isn't a multiple of 5 bits, so the last bits of every word stay unused
+        let mut indices = PackedIndices::new(5, LEN);
+
+        for index in 0..LEN {
+            indices.set(index, index % 32);
+        }
+
+        for index in 0..LEN {
+            assert_eq!(indices.get(index), index % 32);
+        }
+
+        let repacked = indices.repacked(7, LEN);
+        for index in 0..LEN {
+            assert_eq!(repacked.get(index), index % 32);
+        }
+    }
+
+    #[test]
+    fn same_blocks_are_uniform() {
+        let (_, blocks) = test_blocks();
+
+        let paletted = PalettedBlocks::from_fn(LEN, |_| blocks[0].clone());
+        assert!(is_uniform(&paletted));
+        assert_eq!(paletted.get(LEN - 1), &blocks[0]);
+        assert_eq!(paletted.count(|block| *block == blocks[0]), LEN as u32);
+    }
+
+    #[test]
+    fn set_grows_and_shrinks_the_palette() {
+        let (_, blocks) = test_blocks();
+
+        let mut paletted = PalettedBlocks::uniform(LEN, BlockData::none());
+
+        // Enough different blocks that the indices need to be repacked twice
+        for index in 0..LEN {
+            let previous = paletted.set(index, blocks[index % blocks.len()].clone());
+            assert_eq!(previous, BlockData::none());
+        }
+
+        for index in 0..LEN {
+            assert_eq!(paletted.get(index), &blocks[index % blocks.len()]);
+        }
+
+        assert_eq!(paletted.count(|block| *block == blocks[1]), 20);
+        assert_eq!(paletted.count(|block| block.is_none()), 0);
+
+        for index in 0..LEN {
+            paletted.set(index, blocks[2].clone());
+        }
+
+        assert!(is_uniform(&paletted));
+        assert_eq!(paletted.get(0), &blocks[2]);
+    }
+
+    #[test]
+    fn encoding_round_trips() {
+        let directory = std::env::temp_dir().join(format!(
+            "excavatemanufacturate-palette-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let (block_registry, blocks) = test_blocks();
+        let block_ids = BlockIdTable::open(&directory, &block_registry, None).unwrap();
+
+        let uniform = PalettedBlocks::uniform(LEN, blocks[3].clone());
+        let paletted = PalettedBlocks::from_fn(LEN, |index| match index % 3 {
+            0 => BlockData::none(),
+            _ => blocks[index % blocks.len()].clone(),
+        });
+
+        for original in [uniform, paletted] {
+            let mut bytes = Vec::new();
+            original.encode(&mut bytes, &block_ids);
+
+            let decoded = PalettedBlocks::decode(LEN, &bytes, &block_ids).unwrap();
+            for index in 0..LEN {
+                assert_eq!(decoded.get(index), original.get(index));
+            }
+
+            // Anything after the indices means the data is broken
+            bytes.push(0);
+            assert!(PalettedBlocks::decode(LEN, &bytes, &block_ids).is_err());
+
+            bytes.truncate(bytes.len() - 2);
+            assert!(PalettedBlocks::decode(LEN, &bytes, &block_ids).is_err());
+        }
+
+        assert!(PalettedBlocks::decode(LEN, &[2], &block_ids).is_err());
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,122 @@
+use crate::util::{block_pos::BlockPos, chunk_pos::ChunkPos};
+
+use super::{block::BlockData, chunk::ChunkData};
+
+/// The stages a chunk is generated in, in order. A chunk only advances to a stage once all of its neighbors have
+/// finished the stage before, so work that reaches into the chunks around it, like features and light, sees them far
+/// enough along.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GenerationStage {
+    /// The shape of the terrain, as stone and air.
+    Noise,
+
+    /// The blocks that cover the ground and the plants that grow on it, depending on the biome.
+    Surface,
+
+    /// Caves.
+    Carvers,
+
+    /// Ores, the features of mods, and features that reach into the chunks around them, like trees.
+    Features,
+
+    /// Light and heightmaps. Chunks that finished this stage are complete and added to the world.
+    Lighting,
+}
+
+impl GenerationStage {
+    pub const ALL: [GenerationStage; 5] = [
+        GenerationStage::Noise,
+        GenerationStage::Surface,
+        GenerationStage::Carvers,
+        GenerationStage::Features,
+        GenerationStage::Lighting,
+    ];
+
+    pub fn next(self) -> Option<Self> {
+        Self::ALL.get(self as usize + 1).copied()
+    }
+
+    pub fn previous(self) -> Option<Self> {
+        (self as usize).checked_sub(1).map(|index| Self::ALL[index])
+    }
+}
+
+/// A chunk on its way through generation, see [`GenerationStage`].
+pub struct ProtoChunk {
+    /// The last stage the chunk finished, `None` before the first.
+    stage: Option<GenerationStage>,
+
+    /// Whether a task is running the chunk's next stage.
+    in_progress: bool,
+
+    /// The blocks generated so far. `None` while a task works on them, and once the chunk is complete, when they
+    /// belong to the world.
+    chunk_data: Option<ChunkData>,
+
+    /// Blocks that features of other chunks placed into this chunk. They're placed right before lighting, once every
+    /// neighbor has placed its features.
+    feature_blocks: Vec<(BlockPos, BlockData)>,
+}
+
+impl ProtoChunk {
+    pub fn new() -> Self {
+        Self {
+            stage: None,
+            in_progress: false,
+            chunk_data: None,
+            feature_blocks: Vec::new(),
+        }
+    }
+
+    pub fn stage(&self) -> Option<GenerationStage> {
+        self.stage
+    }
+
+    /// Whether the chunk finished its last stage, see [`GenerationStage::Lighting`].
+    pub fn is_complete(&self) -> bool {
+        self.stage == Some(GenerationStage::Lighting)
+    }
+
+    pub fn is_in_progress(&self) -> bool {
+        self.in_progress
+    }
+
+    /// The stage the chunk advances to next, `None` once it's complete.
+    pub fn next_stage(&self) -> Option<GenerationStage> {
+        match self.stage {
+            Some(stage) => stage.next(),
+            None => Some(GenerationStage::Noise),
+        }
+    }
+
+    /// Hands the blocks to a task that runs the next stage.
+    pub fn start_stage(&mut self) -> Option<ChunkData> {
+        self.in_progress = true;
+        self.chunk_data.take()
+    }
+
+    /// Takes the blocks back from the task that ran a stage. Complete chunks hand them on to the world instead.
+    pub fn finish_stage(&mut self, stage: GenerationStage, chunk_data: Option<ChunkData>) {
+        self.in_progress = false;
+        self.stage = Some(stage);
+        self.chunk_data = chunk_data;
+    }
+
+    /// Queues blocks that features of another chunk placed into this chunk.
+    pub fn receive_feature_blocks(&mut self, blocks: Vec<(BlockPos, BlockData)>) {
+        self.feature_blocks.extend(blocks);
+    }
+
+    /// Removes and returns the blocks that features of other chunks placed into this chunk.
+    pub fn take_feature_blocks(&mut self) -> Vec<(BlockPos, BlockData)> {
+        std::mem::take(&mut self.feature_blocks)
+    }
+}
+
+/// The 26 chunks around a chunk.
+pub fn neighbors(chunk_pos: ChunkPos) -> impl Iterator<Item = ChunkPos> {
+    (-1..=1)
+        .flat_map(|x| (-1..=1).flat_map(move |y| (-1..=1).map(move |z| ChunkPos::new(x, y, z))))
+        .filter(|&offset| offset != ChunkPos::new(0, 0, 0))
+        .map(move |offset| chunk_pos + offset)
+}
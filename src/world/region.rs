@@ -0,0 +1,393 @@
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::Once,
+};
+
+use bevy::prelude::*;
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+
+use crate::util::chunk_pos::ChunkPos;
+
+use super::{block_id_table::BlockIdTable, chunk::ChunkData};
+
+/// The number of chunks along each axis of a region.
+pub const REGION_SIZE: i32 = 8;
+pub const REGION_VOLUME: usize = (REGION_SIZE * REGION_SIZE * REGION_SIZE) as usize;
+
+const REGION_MAGIC: [u8; 4] = *b"EMRG";
+const REGION_FORMAT_VERSION: u32 = 2;
+
+/// The format version of region files from before chunks were palette-compressed. Their chunks can't be read anymore,
+/// so these files are treated as if they didn't exist, and replaced once a chunk in them is saved.
+const OUTDATED_REGION_FORMAT_VERSION: u32 = 1;
+static OUTDATED_REGION_WARNING: Once = Once::new();
+
+/// Magic bytes, format version, then an `(offset, length)` pair of `u32`s for every chunk in the region.
+const HEADER_SIZE: usize = 8 + REGION_VOLUME * 8;
+
+/// The position of a region, which is a cube of [`REGION_SIZE`] chunks along each axis.
+#[derive(Clone, Copy, Deref, DerefMut, PartialEq, Eq, Hash)]
+pub struct RegionPos(IVec3);
+
+impl RegionPos {
+    pub fn file_name(self) -> String {
+        format!("r.{}.{}.{}.emr", self.x, self.y, self.z)
+    }
+}
+
+impl From<ChunkPos> for RegionPos {
+    fn from(value: ChunkPos) -> Self {
+        Self(value.inner().div_euclid(IVec3::splat(REGION_SIZE)))
+    }
+}
+
+impl std::fmt::Debug for RegionPos {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "RegionPos({}, {}, {})",
+            self.x, self.y, self.z
+        ))
+    }
+}
+
+/// Index of a chunk into the region's offset table.
+fn region_index(chunk_pos: ChunkPos) -> usize {
+    let offset = chunk_pos.inner().rem_euclid(IVec3::splat(REGION_SIZE));
+
+    (offset.z * REGION_SIZE * REGION_SIZE + offset.y * REGION_SIZE + offset.x) as usize
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Reads and validates the offset table at the start of a region file. Returns `None` for outdated region files, see
+/// [`OUTDATED_REGION_FORMAT_VERSION`].
+fn read_header(file: &mut File) -> io::Result<Option<Vec<(u32, u32)>>> {
+    let mut header = vec![0; HEADER_SIZE];
+    file.read_exact(&mut header)?;
+
+    if header[0..4] != REGION_MAGIC {
+        return Err(invalid_data("Not a region file"));
+    }
+
+    let version = u32::from_le_bytes(header[4..8].try_into().unwrap());
+    if version == OUTDATED_REGION_FORMAT_VERSION {
+        OUTDATED_REGION_WARNING.call_once(|| {
+            warn!("The world contains region files from an older version, their chunks will be generated again");
+        });
+
+        return Ok(None);
+    }
+
+    if version != REGION_FORMAT_VERSION {
+        return Err(invalid_data(format!(
+            "Unsupported region format version {}",
+            version
+        )));
+    }
+
+    Ok(Some(
+        header[8..]
+            .chunks_exact(8)
+            .map(|entry| {
+                (
+                    u32::from_le_bytes(entry[0..4].try_into().unwrap()),
+                    u32::from_le_bytes(entry[4..8].try_into().unwrap()),
+                )
+            })
+            .collect(),
+    ))
+}
+
+/// An in-memory copy of a region file. Chunks are kept compressed, so rewriting a region only costs compressing the
+/// chunks that actually changed.
+pub struct RegionFile {
+    chunks: Vec<Option<Vec<u8>>>,
+}
+
+impl RegionFile {
+    pub fn empty() -> Self {
+        Self {
+            chunks: vec![None; REGION_VOLUME],
+        }
+    }
+
+    /// Reads an entire region file, or returns an empty region if the file doesn't exist yet or is outdated.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::empty()),
+            Err(err) => return Err(err),
+        };
+
+        let Some(header) = read_header(&mut file)? else {
+            return Ok(Self::empty());
+        };
+        let mut chunks = Vec::with_capacity(REGION_VOLUME);
+
+        for (offset, length) in header {
+            if length == 0 {
+                chunks.push(None);
+                continue;
+            }
+
+            let mut compressed = vec![0; length as usize];
+            file.seek(SeekFrom::Start(offset as u64))?;
+            file.read_exact(&mut compressed)?;
+
+            chunks.push(Some(compressed));
+        }
+
+        Ok(Self { chunks })
+    }
+
+    /// Reads a single chunk from a region file without loading the rest of the region. Returns `None` if either the
+    /// region file or the chunk within it doesn't exist.
+    pub fn read_chunk(
+        path: &Path,
+        chunk_pos: ChunkPos,
+        block_ids: &BlockIdTable,
+    ) -> io::Result<Option<ChunkData>> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        let Some(header) = read_header(&mut file)? else {
+            return Ok(None);
+        };
+        let (offset, length) = header[region_index(chunk_pos)];
+
+        if length == 0 {
+            return Ok(None);
+        }
+
+        let mut compressed = vec![0; length as usize];
+        file.seek(SeekFrom::Start(offset as u64))?;
+        file.read_exact(&mut compressed)?;
+
+        let mut bytes = Vec::new();
+        ZlibDecoder::new(compressed.as_slice()).read_to_end(&mut bytes)?;
+
+        ChunkData::decode(&bytes, block_ids).map(Some)
+    }
+
+    pub fn set_chunk(
+        &mut self,
+        chunk_pos: ChunkPos,
+        chunk_data: &ChunkData,
+        block_ids: &BlockIdTable,
+    ) -> io::Result<()> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(&chunk_data.encode(block_ids))?;
+
+        self.chunks[region_index(chunk_pos)] = Some(encoder.finish()?);
+
+        Ok(())
+    }
+
+    /// Writes the region to disk. The region is written to a temporary file first and then moved into place, so
+    /// chunk loading tasks never observe a partially written region.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut header = Vec::with_capacity(HEADER_SIZE);
+        header.extend_from_slice(&REGION_MAGIC);
+        header.extend_from_slice(&REGION_FORMAT_VERSION.to_le_bytes());
+
+        let mut body = Vec::new();
+
+        for chunk in self.chunks.iter() {
+            let (offset, length) = match chunk {
+                Some(compressed) => {
+                    let offset = HEADER_SIZE + body.len();
+                    body.extend_from_slice(compressed);
+
+                    (offset as u32, compressed.len() as u32)
+                }
+                None => (0, 0),
+            };
+
+            header.extend_from_slice(&offset.to_le_bytes());
+            header.extend_from_slice(&length.to_le_bytes());
+        }
+
+        let temp_path = PathBuf::from(format!("{}.tmp", path.display()));
+
+        let mut file = File::create(&temp_path)?;
+        file.write_all(&header)?;
+        file.write_all(&body)?;
+        file.sync_all()?;
+
+        std::fs::rename(temp_path, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::{
+        block::{registry::BlockRegistry, BlockData, BlockName},
+        chunk::CHUNK_VOLUME,
+    };
+
+    use super::*;
+
+    fn test_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!(
+            "excavatemanufacturate-{}-{}",
+            name,
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        directory
+    }
+
+    fn test_chunk(block_registry: &BlockRegistry) -> ChunkData {
+        let stone = block_registry
+            .create_block(&BlockName("test/stone".into()))
+            .unwrap();
+        let glass = block_registry
+            .create_block(&BlockName("test/glass".into()))
+            .unwrap();
+
+        ChunkData::with_data(|offset| match (offset.x + offset.y * 3 + offset.z) % 4 {
+            0 => BlockData::some(stone.clone()),
+            1 => BlockData::some(glass.clone()),
+            _ => BlockData::none(),
+        })
+    }
+
+    fn assert_same_blocks(a: &ChunkData, b: &ChunkData) {
+        for index in 0..CHUNK_VOLUME {
+            let offset = ChunkData::deindexify(index);
+            assert_eq!(a.get_from_raw_offset(offset), b.get_from_raw_offset(offset));
+        }
+    }
+
+    #[test]
+    fn region_pos_rounds_down() {
+        assert_eq!(
+            *RegionPos::from(ChunkPos::new(-1, 0, REGION_SIZE)),
+            IVec3::new(-1, 0, 1)
+        );
+        assert_eq!(
+            *RegionPos::from(ChunkPos::new(
+                -REGION_SIZE,
+                REGION_SIZE - 1,
+                -REGION_SIZE - 1
+            )),
+            IVec3::new(-1, 0, -2)
+        );
+    }
+
+    #[test]
+    fn chunks_survive_writing_and_reading() {
+        let directory = test_directory("region-round-trip");
+        let path = directory.join(RegionPos::from(ChunkPos::new(0, 0, 0)).file_name());
+
+        let block_registry = BlockRegistry::for_tests(&[
+            ("stone", "(textures: (top: \"stone\"), hardness: Hand)"),
+            (
+                "glass",
+                "(textures: (top: \"glass\"), hardness: Hand, transparent: true)",
+            ),
+        ]);
+        let block_ids = BlockIdTable::open(&directory, &block_registry, None).unwrap();
+
+        let chunk_pos = ChunkPos::new(3, 1, 7);
+        let chunk_data = test_chunk(&block_registry);
+
+        let mut region = RegionFile::empty();
+        region
+            .set_chunk(chunk_pos, &chunk_data, &block_ids)
+            .unwrap();
+        region.write(&path).unwrap();
+
+        let read = RegionFile::read_chunk(&path, chunk_pos, &block_ids)
+            .unwrap()
+            .unwrap();
+        assert_same_blocks(&chunk_data, &read);
+
+        // Another chunk of the same region was never saved
+        assert!(
+            RegionFile::read_chunk(&path, ChunkPos::new(3, 1, 6), &block_ids)
+                .unwrap()
+                .is_none()
+        );
+
+        // Opening the region keeps the chunk when it's written again
+        let mut region = RegionFile::open(&path).unwrap();
+        region
+            .set_chunk(ChunkPos::new(0, 0, 0), &ChunkData::empty(), &block_ids)
+            .unwrap();
+        region.write(&path).unwrap();
+
+        let read = RegionFile::read_chunk(&path, chunk_pos, &block_ids)
+            .unwrap()
+            .unwrap();
+        assert_same_blocks(&chunk_data, &read);
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn missing_and_outdated_regions_are_empty() {
+        let directory = test_directory("region-outdated");
+        let path = directory.join(RegionPos::from(ChunkPos::new(0, 0, 0)).file_name());
+
+        let block_registry = BlockRegistry::for_tests(&[]);
+        let block_ids = BlockIdTable::open(&directory, &block_registry, None).unwrap();
+
+        assert!(
+            RegionFile::read_chunk(&path, ChunkPos::new(0, 0, 0), &block_ids)
+                .unwrap()
+                .is_none()
+        );
+
+        let mut header = vec![0; HEADER_SIZE];
+        header[0..4].copy_from_slice(&REGION_MAGIC);
+        header[4..8].copy_from_slice(&OUTDATED_REGION_FORMAT_VERSION.to_le_bytes());
+        // The first chunk points past the header, which an outdated file must not be read for
+        header[8..12].copy_from_slice(&(HEADER_SIZE as u32).to_le_bytes());
+        header[12..16].copy_from_slice(&16u32.to_le_bytes());
+        std::fs::write(&path, &header).unwrap();
+
+        assert!(
+            RegionFile::read_chunk(&path, ChunkPos::new(0, 0, 0), &block_ids)
+                .unwrap()
+                .is_none()
+        );
+        assert!(RegionFile::open(&path)
+            .unwrap()
+            .chunks
+            .iter()
+            .all(Option::is_none));
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn rejects_files_that_arent_regions() {
+        let directory = test_directory("region-invalid");
+        let path = directory.join("r.0.0.0.emr");
+
+        let mut header = vec![0; HEADER_SIZE];
+        header[0..4].copy_from_slice(b"NOPE");
+        std::fs::write(&path, &header).unwrap();
+
+        let err = RegionFile::open(&path).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        header[0..4].copy_from_slice(&REGION_MAGIC);
+        header[4..8].copy_from_slice(&(REGION_FORMAT_VERSION + 1).to_le_bytes());
+        std::fs::write(&path, &header).unwrap();
+
+        let err = RegionFile::open(&path).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -1,6 +1,12 @@
+use std::sync::Arc;
+
 use bevy::{
+    pbr::MeshPipelineKey,
     prelude::*,
-    render::{mesh::MeshVertexAttribute, render_resource::AsBindGroup},
+    render::{
+        mesh::MeshVertexAttribute, render_asset::RenderAssetUsages, render_resource::AsBindGroup,
+    },
+    tasks::Task,
     utils::{HashMap, HashSet},
 };
 use bevy_rapier3d::{
@@ -14,11 +20,14 @@ use crate::{
     util::{
         block_pos::BlockPos,
         chunk_pos::{ChunkPos, LocalChunkPos},
+        mesh::{self, ChunkMeshBuilder, MeshingMode},
     },
 };
 
 use super::{
     block::registry::{BlockRegistryResource, TextureAtlasHandle},
+    chunk::{ChunkData, ChunkNeighborhood},
+    generation::GetTaskPool,
     render_distance::RenderDistance,
     world_access::ExcavateManufacturateWorld,
 };
@@ -34,6 +43,32 @@ pub struct PossiblySpawnedChunks(HashSet<ChunkPos>);
 #[derive(Resource, Deref, DerefMut)]
 pub struct ChunkMeshes(HashMap<ChunkPos, Handle<Mesh>>);
 
+/// Tags every mesh task with a unique generation, so only the result of the latest task for a chunk is applied.
+#[derive(Resource, Default)]
+pub struct ChunkMeshGenerations {
+    latest: HashMap<ChunkPos, u64>,
+    next_generation: u64,
+}
+
+impl ChunkMeshGenerations {
+    fn start_task(&mut self, chunk_pos: ChunkPos) -> u64 {
+        let generation = self.next_generation;
+        self.next_generation += 1;
+
+        self.latest.insert(chunk_pos, generation);
+        generation
+    }
+
+    fn is_latest(&self, chunk_pos: ChunkPos, generation: u64) -> bool {
+        self.latest.get(&chunk_pos) == Some(&generation)
+    }
+
+    /// Makes the results of all running tasks for the chunk outdated.
+    fn forget(&mut self, chunk_pos: ChunkPos) {
+        self.latest.remove(&chunk_pos);
+    }
+}
+
 #[derive(Resource, Deref, DerefMut)]
 pub struct ChunkSpawnQueue(SegQueue<ChunkPos>);
 
@@ -50,6 +85,7 @@ pub fn setup(mut commands: Commands) {
     commands.insert_resource(SpawnedChunks(HashMap::new()));
     commands.insert_resource(PossiblySpawnedChunks(HashSet::new()));
     commands.insert_resource(ChunkMeshes(HashMap::new()));
+    commands.init_resource::<ChunkMeshGenerations>();
     commands.insert_resource(ChunkSpawnQueue(SegQueue::new()));
 
     info!("Setup chunk renderer");
@@ -58,13 +94,20 @@ pub fn setup(mut commands: Commands) {
 pub fn cleanup(
     mut commands: Commands,
     chunks_query: Query<&ChunkPos>,
+    mesh_tasks_query: Query<Entity, With<ChunkMeshTask>>,
     spawned_chunks: ResMut<SpawnedChunks>,
 ) {
     commands.remove_resource::<SpawnedChunks>();
     commands.remove_resource::<PossiblySpawnedChunks>();
     commands.remove_resource::<ChunkMeshes>();
+    commands.remove_resource::<ChunkMeshGenerations>();
     commands.remove_resource::<ChunkSpawnQueue>();
 
+    // Results of these tasks would never be applied anyway
+    for entity in mesh_tasks_query.iter() {
+        commands.entity(entity).despawn();
+    }
+
     despawn_all_chunks(commands, chunks_query, spawned_chunks);
 
     info!("Cleaned up chunk renderer");
@@ -104,68 +147,237 @@ pub fn populate_chunk_spawn_queue(
     }
 }
 
-#[allow(clippy::too_many_arguments)]
-pub fn spawn_chunks<const COUNT: usize>(
+pub struct ChunkMeshResult {
+    chunk_pos: ChunkPos,
+    generation: u64,
+
+    /// The chunk that was meshed, used to detect whether it was modified while the task was running.
+    chunk_data: Arc<ChunkData>,
+    mesh: Mesh,
+
+    /// Blended geometry, or `None` if the chunk doesn't contain translucent blocks.
+    translucent_mesh: Option<Mesh>,
+    collider: Option<Collider>,
+}
+
+/// The translucent geometry of a chunk, which is a child of the chunk entity. Its quads are sorted back to front
+/// whenever the player moves into another block.
+#[derive(Component)]
+pub struct TranslucentChunkMesh {
+    /// Where the chunk's mesh starts, in world space.
+    origin: Vec3,
+
+    /// The block the player was in when the quads were last sorted.
+    sorted_from: Option<BlockPos>,
+}
+
+#[derive(Component)]
+pub struct ChunkMeshTask(Task<ChunkMeshResult>);
+
+/// Starts meshing up to `COUNT` queued chunks on the task pool. The mesh and the collider are built from a snapshot
+/// of the chunk and its neighbors, so the world can keep changing while the task runs.
+pub fn spawn_chunk_mesh_tasks<T: GetTaskPool, const COUNT: usize>(
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ChunkMaterial>>,
-    mut spawned_chunks: ResMut<SpawnedChunks>,
-    mut chunk_meshes: ResMut<ChunkMeshes>,
+    mut chunk_mesh_generations: ResMut<ChunkMeshGenerations>,
     em_world: Res<ExcavateManufacturateWorld>,
     chunk_spawn_queue: Res<ChunkSpawnQueue>,
     block_registry: Res<BlockRegistryResource>,
-    texture_atlas_handle: Res<TextureAtlasHandle>,
+    meshing_mode: Res<MeshingMode>,
 ) {
+    let thread_pool = T::get();
+
     for _ in 0..COUNT {
         let Some(chunk_pos) = chunk_spawn_queue.pop() else {
             return;
         };
 
-        let Some(chunk) = em_world.get_chunk(chunk_pos) else {
+        let Some(neighborhood) = ChunkNeighborhood::snapshot(&em_world, chunk_pos) else {
             // The chunk doesn't exist in the world for some reason
             continue;
         };
 
-        if chunk.is_empty() {
+        // Any task that was started for this chunk before is outdated now
+        let generation = chunk_mesh_generations.start_task(chunk_pos);
+
+        let block_registry = Arc::clone(&block_registry);
+        let meshing_mode = *meshing_mode;
+
+        let task = thread_pool.spawn(async move {
+            let chunk_pos = neighborhood.chunk_pos();
+            let chunk_data = Arc::clone(neighborhood.center());
+
+            if chunk_data.is_empty() {
+                return ChunkMeshResult {
+                    chunk_pos,
+                    generation,
+                    chunk_data,
+                    mesh: ChunkMeshBuilder::new().into_mesh(),
+                    translucent_mesh: None,
+                    collider: None,
+                };
+            }
+
+            let chunk_geometry = neighborhood.get_mesh(&block_registry, meshing_mode);
+
+            let translucent_mesh = (!chunk_geometry.translucent.is_empty()).then(|| {
+                let mut mesh = chunk_geometry.translucent.into_mesh();
+
+                // Kept around in the main world, so it can be sorted again when the player moves
+                mesh.asset_usage = RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD;
+                mesh
+            });
+
+            let collision = chunk_geometry.collision;
+            let collider = (!collision.is_empty())
+                .then(|| Collider::trimesh(collision.vertices, collision.indices));
+
+            ChunkMeshResult {
+                chunk_pos,
+                generation,
+                chunk_data,
+                mesh: chunk_geometry.opaque.into_mesh(),
+                translucent_mesh,
+                collider,
+            }
+        });
+
+        commands.spawn(ChunkMeshTask(task));
+    }
+}
+
+/// Applies finished chunk meshes. Results are dropped if a newer task was started for the same chunk, or if the
+/// chunk was modified while the task was running, since a remesh is queued on every modification anyway.
+#[allow(clippy::too_many_arguments)]
+pub fn poll_chunk_mesh_tasks(
+    mut commands: Commands,
+    mut tasks: Query<(Entity, &mut ChunkMeshTask)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ChunkMaterial>>,
+    mut spawned_chunks: ResMut<SpawnedChunks>,
+    mut chunk_meshes: ResMut<ChunkMeshes>,
+    chunk_mesh_generations: Res<ChunkMeshGenerations>,
+    em_world: Res<ExcavateManufacturateWorld>,
+    texture_atlas_handle: Res<TextureAtlasHandle>,
+) {
+    for (task_entity, mut task) in tasks.iter_mut() {
+        let Some(result) = bevy::tasks::block_on(futures_lite::future::poll_once(&mut task.0))
+        else {
+            continue;
+        };
+
+        commands.entity(task_entity).despawn();
+
+        let chunk_pos = result.chunk_pos;
+
+        if !chunk_mesh_generations.is_latest(chunk_pos, result.generation)
+            || !em_world.is_chunk_unchanged(chunk_pos, &result.chunk_data)
+        {
+            continue;
+        }
+
+        if result.chunk_data.is_empty() {
             // Remove mesh data from the stored meshes
             chunk_meshes.remove(&chunk_pos);
 
-            // Don't spawn a chunk with no data in it
+            // Don't keep a chunk with no data in it around
+            if let Some(old_chunk) = spawned_chunks.remove(&chunk_pos) {
+                commands.entity(old_chunk).despawn_recursive();
+            }
+
             continue;
         }
 
-        let mesh = chunk.get_mesh(chunk_pos, &block_registry, &em_world);
+        let mesh_handle = meshes.add(result.mesh);
+        chunk_meshes.insert(chunk_pos, mesh_handle.clone_weak());
 
-        let collider = Collider::from_bevy_mesh(
-            &mesh,
-            &bevy_rapier3d::geometry::ComputedColliderShape::TriMesh,
-        )
-        .expect("Chunk mesh should be able to be converted into a collider");
+        let origin = BlockPos::from(chunk_pos).as_vec3() - 1.0;
 
-        let mesh_handle = meshes.add(mesh);
+        let mut entity_commands = if let Some(&entity) = spawned_chunks.get(&chunk_pos) {
+            // Update the existing chunk in place, so an enabled collider stays enabled
+            let mut entity_commands = commands.entity(entity);
+            entity_commands.insert(mesh_handle).despawn_descendants();
 
-        chunk_meshes.insert(chunk_pos, mesh_handle.clone_weak());
+            match result.collider {
+                Some(collider) => entity_commands.insert(collider),
+                None => entity_commands.remove::<Collider>(),
+            };
 
-        let entity = commands
-            .spawn((
+            entity_commands
+        } else {
+            let mut entity_commands = commands.spawn((
                 MaterialMeshBundle {
                     mesh: mesh_handle,
-                    material: materials.add(ChunkMaterial {
-                        atlas_texture: Some(texture_atlas_handle.clone_weak()),
-                    }),
-                    transform: Transform::from_translation(
-                        BlockPos::from(chunk_pos).as_vec3() - 1.0,
-                    ),
+                    material: materials
+                        .add(ChunkMaterial::opaque(texture_atlas_handle.clone_weak())),
+                    transform: Transform::from_translation(origin),
                     ..Default::default()
                 },
                 chunk_pos,
-            ))
+            ));
+
             // Physics components
-            .insert((collider, RigidBody::Fixed, ColliderDisabled))
-            .id();
+            entity_commands.insert((RigidBody::Fixed, ColliderDisabled));
+
+            if let Some(collider) = result.collider {
+                entity_commands.insert(collider);
+            }
+
+            spawned_chunks.insert(chunk_pos, entity_commands.id());
+            entity_commands
+        };
 
-        if let Some(old_chunk) = spawned_chunks.insert(chunk_pos, entity) {
-            commands.entity(old_chunk).despawn();
+        if let Some(translucent_mesh) = result.translucent_mesh {
+            entity_commands.with_children(|chunk| {
+                chunk.spawn((
+                    MaterialMeshBundle {
+                        mesh: meshes.add(translucent_mesh),
+                        material: materials.add(ChunkMaterial::translucent(
+                            texture_atlas_handle.clone_weak(),
+                        )),
+                        ..Default::default()
+                    },
+                    TranslucentChunkMesh {
+                        origin,
+                        sorted_from: None,
+                    },
+                ));
+            });
+        }
+    }
+}
+
+/// Sorts the quads of translucent chunk meshes back to front as seen from the player, whenever the player moves into
+/// another block or a mesh is new.
+pub fn sort_translucent_chunk_meshes(
+    player_query: Query<&Transform, With<Player>>,
+    mut translucent_query: Query<(&Handle<Mesh>, &mut TranslucentChunkMesh)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let viewer = player_query.single().translation;
+    let viewer_block_pos = BlockPos::from(viewer);
+
+    for (mesh_handle, mut translucent_mesh) in translucent_query.iter_mut() {
+        if translucent_mesh.sorted_from == Some(viewer_block_pos) {
+            continue;
+        }
+
+        if let Some(mesh) = meshes.get_mut(mesh_handle) {
+            mesh::sort_quads_back_to_front(mesh, viewer - translucent_mesh.origin);
+        }
+
+        translucent_mesh.sorted_from = Some(viewer_block_pos);
+    }
+}
+
+/// Rebuilds every spawned chunk when the meshing mode is switched, so the two modes can be compared in place.
+pub fn remesh_on_meshing_mode_change(
+    meshing_mode: Res<MeshingMode>,
+    spawned_chunks: Res<SpawnedChunks>,
+    chunk_spawn_queue: Res<ChunkSpawnQueue>,
+) {
+    if meshing_mode.is_changed() && !meshing_mode.is_added() {
+        for &chunk_pos in spawned_chunks.keys() {
+            chunk_spawn_queue.push(chunk_pos);
         }
     }
 }
@@ -177,6 +389,7 @@ pub fn despawn_chunks(
     render_distance: Res<RenderDistance>,
     mut spawned_chunks: ResMut<SpawnedChunks>,
     mut possibly_spawned_chunks: ResMut<PossiblySpawnedChunks>,
+    mut chunk_mesh_generations: ResMut<ChunkMeshGenerations>,
 ) {
     let player_chunk_pos = *player_query.single();
 
@@ -185,8 +398,11 @@ pub fn despawn_chunks(
 
         if !render_distance.contains(local_chunk_pos) {
             if let Some(entity) = spawned_chunks.remove(&chunk_pos) {
-                commands.entity(entity).despawn();
+                commands.entity(entity).despawn_recursive();
                 possibly_spawned_chunks.remove(&chunk_pos);
+
+                // Don't respawn the chunk when a mesh task that is still running for it finishes
+                chunk_mesh_generations.forget(chunk_pos);
             }
         }
     }
@@ -199,7 +415,7 @@ pub fn despawn_all_chunks(
 ) {
     for &chunk_pos in chunks_query.iter() {
         if let Some(entity) = spawned_chunks.remove(&chunk_pos) {
-            commands.entity(entity).despawn();
+            commands.entity(entity).despawn_recursive();
         }
     }
 }
@@ -209,16 +425,52 @@ pub struct ChunkMaterial {
     #[texture(0)]
     #[sampler(1)]
     atlas_texture: Option<Handle<Image>>,
+
+    /// Opaque for the main chunk mesh, blended for translucent geometry.
+    alpha_mode: AlphaMode,
 }
 
 impl ChunkMaterial {
     const SHADER_PATH: &'static str = "excavatemanufacturate/shaders/gbuffer.wgsl";
 
-    const ATTRIBUTE_AO: MeshVertexAttribute = MeshVertexAttribute::new(
+    /// The material of opaque and cutout blocks.
+    pub fn opaque(atlas_texture: Handle<Image>) -> Self {
+        Self {
+            atlas_texture: Some(atlas_texture),
+            alpha_mode: AlphaMode::Opaque,
+        }
+    }
+
+    /// The material of translucent blocks, which blends them over what's behind them.
+    pub fn translucent(atlas_texture: Handle<Image>) -> Self {
+        Self {
+            atlas_texture: Some(atlas_texture),
+            alpha_mode: AlphaMode::Blend,
+        }
+    }
+
+    /// How unoccluded each vertex is, from 0 for fully occluded to 1 for not occluded at all.
+    pub const ATTRIBUTE_AO: MeshVertexAttribute = MeshVertexAttribute::new(
         "ambient_occlusion",
         139205343,
         bevy::render::render_resource::VertexFormat::Float32,
     );
+
+    /// The area of the texture atlas that a face samples from, see [`ChunkMeshBuilder::add_quad`].
+    ///
+    /// [`ChunkMeshBuilder::add_quad`]: crate::util::mesh::ChunkMeshBuilder::add_quad
+    pub const ATTRIBUTE_ATLAS_RECT: MeshVertexAttribute = MeshVertexAttribute::new(
+        "atlas_rect",
+        582914207,
+        bevy::render::render_resource::VertexFormat::Float32x4,
+    );
+
+    /// The sky light and block light that shine on a face, from 0 for darkness to 1 for the brightest light.
+    pub const ATTRIBUTE_LIGHT: MeshVertexAttribute = MeshVertexAttribute::new(
+        "light",
+        720462951,
+        bevy::render::render_resource::VertexFormat::Float32x2,
+    );
 }
 
 impl Material for ChunkMaterial {
@@ -230,16 +482,34 @@ impl Material for ChunkMaterial {
         Self::SHADER_PATH.into()
     }
 
+    fn alpha_mode(&self) -> AlphaMode {
+        self.alpha_mode
+    }
+
     fn specialize(
         _pipeline: &bevy::pbr::MaterialPipeline<Self>,
         descriptor: &mut bevy::render::render_resource::RenderPipelineDescriptor,
         layout: &bevy::render::mesh::MeshVertexBufferLayout,
-        _key: bevy::pbr::MaterialPipelineKey<Self>,
+        key: bevy::pbr::MaterialPipelineKey<Self>,
     ) -> Result<(), bevy::render::render_resource::SpecializedMeshPipelineError> {
+        // Translucent geometry keeps the transparency of its texture instead of cutting it out
+        if key
+            .mesh_key
+            .intersection(MeshPipelineKey::BLEND_RESERVED_BITS)
+            == MeshPipelineKey::BLEND_ALPHA
+        {
+            if let Some(fragment) = descriptor.fragment.as_mut() {
+                fragment.shader_defs.push("TRANSLUCENT".into());
+            }
+        }
+
         let vertex_layout = layout.get_layout(&[
             Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
             Mesh::ATTRIBUTE_NORMAL.at_shader_location(1),
             Mesh::ATTRIBUTE_UV_0.at_shader_location(2),
+            Self::ATTRIBUTE_ATLAS_RECT.at_shader_location(3),
+            Self::ATTRIBUTE_AO.at_shader_location(4),
+            Self::ATTRIBUTE_LIGHT.at_shader_location(5),
         ])?;
 
         descriptor.vertex.buffers = vec![vertex_layout];
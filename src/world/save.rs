@@ -0,0 +1,270 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    util::chunk_pos::ChunkPos,
+    world::block::{excavatemanufacturate_blocks::block_names, BlockName},
+};
+
+use super::{
+    block::registry::{BlockRegistry, BlockRegistryResource},
+    block_id_table::BlockIdTable,
+    chunk::ChunkData,
+    region::{RegionFile, RegionPos},
+    seed::WorldSeed,
+    world_access::ExcavateManufacturateWorld,
+};
+
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The file in a world save that stores the settings the world was created with, see [`WorldInfo`].
+pub const WORLD_INFO_FILE_NAME: &str = "world.ron";
+
+/// Which world gets loaded when entering the game. Edited from the main menu.
+#[derive(Resource)]
+pub struct SaveSettings {
+    pub world_name: String,
+
+    /// The seed a new world is created with, see [`WorldSeed::parse`]. Existing worlds keep the seed they were
+    /// created with.
+    pub seed: String,
+
+    /// The block that replaces blocks in the save which no longer exist. Those blocks are removed if this is `None`.
+    pub missing_block: Option<BlockName>,
+}
+
+impl Default for SaveSettings {
+    fn default() -> Self {
+        Self {
+            world_name: String::from("world"),
+            seed: String::new(),
+            missing_block: Some(block_names::MISSING),
+        }
+    }
+}
+
+/// The save of the world that is currently being played.
+#[derive(Resource, Deref)]
+pub struct ActiveWorldSave(Arc<WorldSave>);
+
+/// The settings a world was created with, which stay the same for the whole life of the world.
+#[derive(Serialize, Deserialize)]
+pub struct WorldInfo {
+    pub seed: WorldSeed,
+}
+
+impl WorldInfo {
+    /// Reads the info of the save in `directory`, or creates it with the given seed for a new save. Saves that already
+    /// have chunks from before the info existed get [`WorldSeed::LEGACY`] instead.
+    fn open(directory: &Path, new_world_seed: WorldSeed) -> io::Result<Self> {
+        let path = directory.join(WORLD_INFO_FILE_NAME);
+
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => ron::from_str(&contents)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                let has_chunks = std::fs::read_dir(directory.join("region"))?
+                    .next()
+                    .is_some();
+
+                let seed = if has_chunks {
+                    warn!(
+                        "World save {} has no seed, using the legacy seed. Chunks generated from now on may not line \
+                         up with the ones that were saved before.",
+                        directory.display()
+                    );
+                    WorldSeed::LEGACY
+                } else {
+                    new_world_seed
+                };
+
+                let world_info = Self { seed };
+
+                let contents =
+                    ron::ser::to_string_pretty(&world_info, ron::ser::PrettyConfig::default())
+                        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+                std::fs::write(&path, contents)?;
+
+                Ok(world_info)
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// A world save on disk. Chunks are grouped into region files, see [`RegionFile`], and store blocks with the IDs of
+/// the save's [`BlockIdTable`].
+pub struct WorldSave {
+    directory: PathBuf,
+    info: WorldInfo,
+    block_ids: BlockIdTable,
+}
+
+impl WorldSave {
+    pub fn saves_directory() -> PathBuf {
+        let mut directory = bevy::asset::io::file::FileAssetReader::get_base_path();
+        directory.push("saves");
+
+        directory
+    }
+
+    /// Opens the save with the given name, creating it with `new_world_seed` if it doesn't exist yet.
+    pub fn open(
+        world_name: &str,
+        new_world_seed: WorldSeed,
+        block_registry: &BlockRegistry,
+        missing_block: Option<&BlockName>,
+    ) -> io::Result<Self> {
+        let directory = Self::saves_directory().join(world_name);
+        std::fs::create_dir_all(directory.join("region"))?;
+
+        let info = WorldInfo::open(&directory, new_world_seed)?;
+        let block_ids = BlockIdTable::open(&directory, block_registry, missing_block)?;
+
+        Ok(Self {
+            directory,
+            info,
+            block_ids,
+        })
+    }
+
+    pub fn seed(&self) -> WorldSeed {
+        self.info.seed
+    }
+
+    fn region_path(&self, region_pos: RegionPos) -> PathBuf {
+        let mut path = self.directory.join("region");
+        path.push(region_pos.file_name());
+
+        path
+    }
+
+    /// Loads a single chunk from disk. Returns `None` if the chunk was never saved, or if it couldn't be read, in
+    /// which case the chunk should be generated instead.
+    pub fn load_chunk(&self, chunk_pos: ChunkPos) -> Option<ChunkData> {
+        let path = self.region_path(RegionPos::from(chunk_pos));
+
+        match RegionFile::read_chunk(&path, chunk_pos, &self.block_ids) {
+            Ok(chunk_data) => chunk_data,
+            Err(err) => {
+                error!(
+                    "Failed to load {:?} from {}: {}",
+                    chunk_pos,
+                    path.display(),
+                    err
+                );
+                None
+            }
+        }
+    }
+
+    /// Writes the given chunks to disk, rewriting each touched region file once.
+    pub fn save_chunks<'a>(
+        &self,
+        chunks: impl IntoIterator<Item = (ChunkPos, &'a ChunkData)>,
+    ) -> io::Result<()> {
+        let mut regions: HashMap<RegionPos, Vec<(ChunkPos, &ChunkData)>> = HashMap::new();
+
+        for (chunk_pos, chunk_data) in chunks {
+            regions
+                .entry(RegionPos::from(chunk_pos))
+                .or_default()
+                .push((chunk_pos, chunk_data));
+        }
+
+        for (region_pos, chunks) in regions {
+            let path = self.region_path(region_pos);
+            let mut region = RegionFile::open(&path)?;
+
+            for (chunk_pos, chunk_data) in chunks {
+                region.set_chunk(chunk_pos, chunk_data, &self.block_ids)?;
+            }
+
+            region.write(&path)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub fn init_settings(mut commands: Commands) {
+    commands.init_resource::<SaveSettings>();
+}
+
+pub fn setup(
+    mut commands: Commands,
+    save_settings: Res<SaveSettings>,
+    block_registry: Res<BlockRegistryResource>,
+) {
+    let world_save = WorldSave::open(
+        &save_settings.world_name,
+        WorldSeed::parse(&save_settings.seed),
+        &block_registry,
+        save_settings.missing_block.as_ref(),
+    )
+    .unwrap_or_else(|err| {
+        panic!(
+            "Couldn't open world save \"{}\": {}",
+            save_settings.world_name, err
+        )
+    });
+
+    info!(
+        "Opened world save \"{}\" with seed {}",
+        save_settings.world_name,
+        world_save.seed().0
+    );
+    commands.insert_resource(ActiveWorldSave(Arc::new(world_save)));
+}
+
+pub fn cleanup(mut commands: Commands) {
+    commands.remove_resource::<ActiveWorldSave>();
+    info!("Closed world save");
+}
+
+/// Writes every chunk that was modified since the last save to disk.
+pub fn save_world(
+    mut em_world: ResMut<ExcavateManufacturateWorld>,
+    world_save: Res<ActiveWorldSave>,
+) {
+    let modified_chunk_count = em_world.modified_chunks().count();
+
+    if modified_chunk_count == 0 {
+        return;
+    }
+
+    let chunks = em_world.modified_chunks().filter_map(|chunk_pos| {
+        em_world
+            .get_chunk(chunk_pos)
+            .map(|chunk_data| (chunk_pos, chunk_data))
+    });
+
+    match world_save.save_chunks(chunks) {
+        Ok(()) => {
+            em_world.clear_modified_chunks();
+            info!("Saved {} modified chunks", modified_chunk_count);
+        }
+        // The chunks stay marked as modified, so saving is retried on the next autosave or on exit.
+        Err(err) => error!("Failed to save world: {}", err),
+    }
+}
+
+pub fn autosave_world(
+    em_world: ResMut<ExcavateManufacturateWorld>,
+    world_save: Res<ActiveWorldSave>,
+    time: Res<Time>,
+    mut timer: Local<Option<Timer>>,
+) {
+    let timer = timer.get_or_insert_with(|| Timer::new(AUTOSAVE_INTERVAL, TimerMode::Repeating));
+
+    if timer.tick(time.delta()).just_finished() {
+        save_world(em_world, world_save);
+    }
+}
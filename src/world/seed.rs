@@ -0,0 +1,143 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// The seed of a world, which all of its generation is derived from. Generating a chunk only depends on the seed and
+/// the chunk's position, so a seed always gives the same world, no matter in which order chunks are generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct WorldSeed(pub u64);
+
+impl WorldSeed {
+    /// The seed of saves from before worlds had seeds. Their chunks were generated without one, so no seed gives the
+    /// same terrain, but a fixed one at least keeps the rest of the world the same every time it's opened.
+    pub const LEGACY: WorldSeed = WorldSeed(0);
+
+    /// How far noise offsets reach from the origin of the noise. Larger offsets would lose precision, since noise is
+    /// sampled with `f32` coordinates.
+    const MAX_NOISE_OFFSET: f32 = 10000.0;
+
+    pub fn random() -> Self {
+        Self(rand::random())
+    }
+
+    /// Reads a seed typed in by a player. Numbers are used as they are, any other text is hashed, and an empty text
+    /// gives a random seed.
+    pub fn parse(text: &str) -> Self {
+        let text = text.trim();
+
+        if text.is_empty() {
+            return Self::random();
+        }
+
+        if let Ok(seed) = text.parse::<u64>() {
+            return Self(seed);
+        }
+
+        if let Ok(seed) = text.parse::<i64>() {
+            return Self(seed as u64);
+        }
+
+        // FNV-1a, which unlike the standard library's hasher is guaranteed to stay the same between versions
+        let hash = text.bytes().fold(0xcbf29ce484222325_u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+
+        Self(hash)
+    }
+
+    /// Derives a separate seed for one use of the seed, like a single noise, so different uses don't produce
+    /// patterns that line up. Every use picks its own `salt`.
+    pub fn derive(self, salt: u64) -> u64 {
+        // SplitMix64, which spreads small differences in the input over all bits of the output
+        let mut x = self.0 ^ salt.wrapping_mul(0x9e3779b97f4a7c15);
+        x = x.wrapping_add(0x9e3779b97f4a7c15);
+        x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+
+        x ^ (x >> 31)
+    }
+
+    /// A point to offset noise sampling positions by, so every seed samples a different part of the noise. Noise
+    /// functions don't take a seed themselves.
+    pub fn noise_offset(self, salt: u64) -> Vec3 {
+        let component = |index: u64| {
+            // The top 24 bits fit into an f32 exactly
+            let unit = (self.derive(salt.wrapping_mul(3).wrapping_add(index)) >> 40) as f32
+                / (1 << 24) as f32;
+
+            (unit * 2.0 - 1.0) * Self::MAX_NOISE_OFFSET
+        };
+
+        Vec3::new(component(0), component(1), component(2))
+    }
+
+    /// Derives a separate seed for one use of the seed at a single position, like [`WorldSeed::derive`].
+    pub fn derive_at(self, salt: u64, position: IVec3) -> u64 {
+        let position_hash = (position.x as u64).wrapping_mul(0x8da6b343)
+            ^ (position.y as u64).wrapping_mul(0xd8163841)
+            ^ (position.z as u64).wrapping_mul(0xcb1ab31f);
+
+        self.derive(salt ^ position_hash.rotate_left(17))
+    }
+
+    /// A random number from 0 to 1 that only depends on the seed, the salt and the position, so it comes out the
+    /// same no matter when or on which thread the position is generated.
+    pub fn random_at(self, salt: u64, position: IVec3) -> f32 {
+        (self.derive_at(salt, position) >> 40) as f32 / (1 << 24) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_parse_as_they_are() {
+        assert_eq!(WorldSeed::parse("12345"), WorldSeed(12345));
+        assert_eq!(WorldSeed::parse("  7 "), WorldSeed(7));
+        assert_eq!(WorldSeed::parse("-1"), WorldSeed(u64::MAX));
+    }
+
+    #[test]
+    fn text_is_hashed_the_same_every_time() {
+        // Saves store the seed, but players share the text, so the hash must never change
+        assert_eq!(WorldSeed::parse("hello"), WorldSeed(0xa430d84680aabd0b));
+        assert_ne!(WorldSeed::parse("hello"), WorldSeed::parse("Hello"));
+    }
+
+    #[test]
+    fn derived_seeds_never_change() {
+        // Changing these changes the terrain of every existing world
+        assert_eq!(WorldSeed(0).derive(0), 0xe220a8397b1dcdaf);
+        assert_eq!(WorldSeed(42).derive(7), 0xcbbd05c7de73a889);
+    }
+
+    #[test]
+    fn derived_seeds_depend_on_seed_and_salt() {
+        let seed = WorldSeed(42);
+
+        assert_ne!(seed.derive(1), seed.derive(2));
+        assert_ne!(seed.derive(1), WorldSeed(43).derive(1));
+
+        let position = IVec3::new(-3, 17, 120);
+        assert_eq!(seed.derive_at(1, position), seed.derive_at(1, position));
+        assert_ne!(seed.derive_at(1, position), seed.derive_at(2, position));
+        assert_ne!(
+            seed.derive_at(1, position),
+            seed.derive_at(1, position + IVec3::X)
+        );
+    }
+
+    #[test]
+    fn derived_values_stay_in_range() {
+        for seed in [WorldSeed(0), WorldSeed(1), WorldSeed(u64::MAX)] {
+            let offset = seed.noise_offset(3);
+            assert!(offset.abs().max_element() <= WorldSeed::MAX_NOISE_OFFSET);
+
+            for x in -50..50 {
+                let random = seed.random_at(5, IVec3::new(x, 0, 0));
+                assert!((0.0..1.0).contains(&random));
+            }
+        }
+    }
+}
@@ -1,39 +1,127 @@
-use bevy::{prelude::*, utils::HashMap};
+use std::{collections::VecDeque, sync::Arc};
 
-use crate::util::{block_pos::BlockPos, chunk_pos::ChunkPos};
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
 
-use super::{block::BlockData, chunk::ChunkData, render::ChunkSpawnQueue};
+use crate::util::{
+    block_pos::BlockPos,
+    chunk_pos::ChunkPos,
+    raytrace::{self, Hit},
+};
+
+use super::{
+    block::{
+        registry::{BlockRegistry, BlockRegistryResource},
+        BlockData,
+    },
+    chunk::ChunkData,
+    heightmap::{self, ColumnHeightmap},
+    light::{self, LightPropagation},
+    render::{ChunkSpawnQueue, PossiblySpawnedChunks, SpawnedChunks},
+    save::ActiveWorldSave,
+    worldgen::biome::{Biome, BiomeSource},
+    CHUNK_SIZE_INT, NUM_CHUNKS_LIT_PER_FRAME,
+};
 
 #[derive(Resource)]
 pub struct ExcavateManufacturateWorld {
-    chunks: HashMap<ChunkPos, ChunkData>,
+    /// Chunks are shared with background tasks such as meshing, and copied on write if a task still holds them.
+    chunks: HashMap<ChunkPos, Arc<ChunkData>>,
+
+    /// Chunks that were added, but don't share light with their neighbors yet, in the order they were added.
+    unconnected_chunks: VecDeque<ChunkPos>,
+
+    /// Chunks that were changed since the world was last saved.
+    modified_chunks: HashSet<ChunkPos>,
+
+    /// Chunks whose meshes need to be rebuilt because the light they show changed.
+    relit_chunks: HashSet<ChunkPos>,
+
+    /// The surface of every column of chunks that has any loaded chunk, keyed by the x and z coordinates of the
+    /// chunks.
+    column_heightmaps: HashMap<IVec2, ColumnHeightmap>,
+
+    biome_source: BiomeSource,
 }
 
 impl ExcavateManufacturateWorld {
-    pub fn new() -> Self {
+    pub fn new(biome_source: BiomeSource) -> Self {
         Self {
             chunks: HashMap::new(),
+            unconnected_chunks: VecDeque::new(),
+            modified_chunks: HashSet::new(),
+            relit_chunks: HashSet::new(),
+            column_heightmaps: HashMap::new(),
+            biome_source,
         }
     }
 
     pub fn get_chunk(&self, chunk_pos: ChunkPos) -> Option<&ChunkData> {
-        self.chunks.get(&chunk_pos)
+        self.chunks.get(&chunk_pos).map(Arc::as_ref)
+    }
+
+    /// Gets a shared handle to the chunk, which stays unchanged even if the chunk in the world is modified.
+    pub fn get_chunk_shared(&self, chunk_pos: ChunkPos) -> Option<Arc<ChunkData>> {
+        self.chunks.get(&chunk_pos).cloned()
+    }
+
+    /// Whether the chunk in the world is still the same as a handle obtained from [`Self::get_chunk_shared`], i.e.
+    /// it wasn't modified or replaced since.
+    pub fn is_chunk_unchanged(&self, chunk_pos: ChunkPos, chunk_data: &Arc<ChunkData>) -> bool {
+        self.chunks
+            .get(&chunk_pos)
+            .is_some_and(|current| Arc::ptr_eq(current, chunk_data))
     }
 
     pub fn get_chunk_mut(&mut self, chunk_pos: ChunkPos) -> Option<&mut ChunkData> {
-        self.chunks.get_mut(&chunk_pos)
+        self.chunks.get_mut(&chunk_pos).map(Arc::make_mut)
     }
 
+    /// Adds a chunk to the world and raises the surface of its column. The chunk has to be lit on its own with
+    /// [`light::light_chunk`] and have its heightmap computed with [`heightmap::compute_heightmap`] before. Light is
+    /// spread between it and its neighbors later on, see [`Self::connect_chunks`].
     pub fn insert_chunk(
         &mut self,
         chunk_pos: ChunkPos,
         chunk_data: ChunkData,
-    ) -> Option<ChunkData> {
-        self.chunks.insert(chunk_pos, chunk_data)
+    ) -> Option<Arc<ChunkData>> {
+        let column_pos = IVec2::new(chunk_pos.x, chunk_pos.z);
+        let column_heightmap = self
+            .column_heightmaps
+            .entry(column_pos)
+            .or_insert_with(ColumnHeightmap::new);
+
+        column_heightmap.add_chunk(chunk_pos.y, &chunk_data);
+
+        let previous_chunk = self.chunks.insert(chunk_pos, Arc::new(chunk_data));
+
+        if previous_chunk.is_some() {
+            // The replaced chunk may have been higher than the new one
+            for x in 0..CHUNK_SIZE_INT {
+                for z in 0..CHUNK_SIZE_INT {
+                    column_heightmap.update_column(column_pos, x, z, &self.chunks);
+                }
+            }
+        }
+
+        self.unconnected_chunks.push_back(chunk_pos);
+
+        previous_chunk
     }
 
-    pub fn chunk_exists(&self, chunk_pos: ChunkPos) -> bool {
-        self.chunks.contains_key(&chunk_pos)
+    /// Spreads light between the chunks that were added the longest time ago and their neighbors, at most
+    /// `max_chunks` of them.
+    pub fn connect_chunks(&mut self, max_chunks: usize, block_registry: &BlockRegistry) {
+        for _ in 0..max_chunks {
+            let Some(chunk_pos) = self.unconnected_chunks.pop_front() else {
+                break;
+            };
+
+            self.light_propagation(block_registry)
+                .connect_chunk(chunk_pos);
+        }
     }
 
     pub fn total_chunk_count(&self) -> usize {
@@ -48,37 +136,123 @@ impl ExcavateManufacturateWorld {
         }
     }
 
-    /// Attempts to set the block at the position. If the block is in a chunk that does not exist and the block is
-    /// not empty, creates a new chunk at that position with just the block inside.
-    pub fn set_block(&mut self, block_pos: BlockPos, block_data: BlockData) -> bool {
-        if let Some(chunk_data) = self.get_chunk_mut(ChunkPos::from(block_pos)) {
+    /// Attempts to set the block at the position, and updates the light around it. If the block is in a chunk that
+    /// does not exist and the block is not empty, creates a new chunk at that position with just the block inside.
+    pub fn set_block(
+        &mut self,
+        block_pos: BlockPos,
+        block_data: BlockData,
+        block_registry: &BlockRegistry,
+    ) -> bool {
+        let chunk_pos = ChunkPos::from(block_pos);
+
+        if let Some(chunk_data) = self.get_chunk_mut(chunk_pos) {
             chunk_data.set(block_pos, block_data);
-            true
-        } else if block_data.is_some() {
-            let chunk_pos = ChunkPos::from(block_pos);
+            heightmap::update_heightmap(chunk_data, block_pos, block_registry);
+
+            let offset = block_pos.as_chunk_offset();
+            let column_pos = IVec2::new(chunk_pos.x, chunk_pos.z);
 
+            // Chunks always have a column heightmap once they are in the world
+            if let Some(column_heightmap) = self.column_heightmaps.get_mut(&column_pos) {
+                column_heightmap.update_column(column_pos, offset.x, offset.z, &self.chunks);
+            }
+
+            self.light_propagation(block_registry)
+                .update_block(block_pos);
+        } else if block_data.is_some() {
             let mut chunk_data = ChunkData::empty();
             chunk_data.set(block_pos, block_data);
+            light::light_chunk(&mut chunk_data, block_registry);
+            heightmap::compute_heightmap(&mut chunk_data, block_registry);
 
             self.insert_chunk(chunk_pos, chunk_data);
-
-            true
         } else {
-            false
+            return false;
         }
+
+        self.modified_chunks.insert(chunk_pos);
+        true
     }
 
-    pub fn hit_evaluator(&self) -> impl Fn(IVec3) -> bool + '_ {
-        |pos: IVec3| {
-            let block_pos = BlockPos::from(pos);
-            self.get_block(block_pos)
-                .is_some_and(|block_data| block_data.is_some())
+    pub fn modified_chunks(&self) -> impl Iterator<Item = ChunkPos> + '_ {
+        self.modified_chunks.iter().copied()
+    }
+
+    pub fn clear_modified_chunks(&mut self) {
+        self.modified_chunks.clear();
+    }
+
+    /// The y coordinate of the highest solid block at the x and z coordinates, among the loaded chunks. Returns
+    /// `None` if no loaded chunk has a solid block there. See [`heightmap::is_solid`] for what counts as solid.
+    pub fn surface_height(&self, x: i32, z: i32) -> Option<i32> {
+        let column_heightmap = self.column_heightmaps.get(&heightmap::column_pos(x, z))?;
+
+        column_heightmap.get(x.rem_euclid(CHUNK_SIZE_INT), z.rem_euclid(CHUNK_SIZE_INT))
+    }
+
+    /// The biome at the position. Biomes only depend on the world seed, so this works for positions that haven't
+    /// been generated yet as well.
+    pub fn biome_at(&self, block_pos: BlockPos) -> Biome {
+        self.biome_source.biome_at(block_pos)
+    }
+
+    fn light_propagation<'a>(
+        &'a mut self,
+        block_registry: &'a BlockRegistry,
+    ) -> LightPropagation<'a> {
+        LightPropagation::new(&mut self.chunks, block_registry, &mut self.relit_chunks)
+    }
+
+    /// Removes and returns the chunks whose light changed since this was last called.
+    pub fn take_relit_chunks(&mut self) -> HashSet<ChunkPos> {
+        std::mem::take(&mut self.relit_chunks)
+    }
+
+    /// Finds where a ray hits the blocks of the world, see [`raytrace::raytrace_dda`]. Rays pass through the parts
+    /// of a block that its model doesn't fill.
+    pub fn hit_evaluator<'a>(
+        &'a self,
+        block_registry: &'a BlockRegistry,
+        ray_pos: Vec3,
+        ray_dir: Vec3,
+    ) -> impl Fn(&Hit) -> Option<Hit> + 'a {
+        move |hit: &Hit| {
+            let block = self.get_block(BlockPos::from(hit.voxel))?.as_ref()?;
+            let model = &block_registry.get_block_data(block.id).model;
+
+            if model.is_cube() {
+                return Some(*hit);
+            }
+
+            // A block at `pos` occupies the space between `pos - 1` and `pos`
+            let block_min = (hit.voxel - 1).as_vec3();
+
+            model
+                .hit_boxes()
+                .iter()
+                .filter_map(|cuboid| {
+                    raytrace::intersect_box(
+                        ray_pos,
+                        ray_dir,
+                        block_min + cuboid.min,
+                        block_min + cuboid.max,
+                    )
+                })
+                .min_by(|(a, _), (b, _)| a.total_cmp(b))
+                .map(|(distance, normal)| Hit {
+                    position: ray_pos + ray_dir * distance,
+                    normal,
+                    voxel: hit.voxel,
+                })
         }
     }
 }
 
-pub fn setup(mut commands: Commands) {
-    commands.insert_resource(ExcavateManufacturateWorld::new());
+pub fn setup(mut commands: Commands, world_save: Res<ActiveWorldSave>) {
+    commands.insert_resource(ExcavateManufacturateWorld::new(BiomeSource::new(
+        world_save.seed(),
+    )));
     info!("Set up world data");
 }
 
@@ -111,9 +285,14 @@ impl BlockDestroyEvent {
 pub fn apply_block_place_events(
     mut events: ResMut<Events<BlockPlaceEvent>>,
     mut em_world: ResMut<ExcavateManufacturateWorld>,
+    block_registry: Res<BlockRegistryResource>,
+    chunk_spawn_queue: Res<ChunkSpawnQueue>,
 ) {
     for event in events.drain() {
-        em_world.set_block(event.pos, event.block);
+        if em_world.set_block(event.pos, event.block, &block_registry) {
+            // Redraw chunks if needed
+            chunk_spawn_queue.submit_on_block_update(event.pos);
+        }
     }
 }
 
@@ -121,6 +300,7 @@ pub fn apply_block_destroy_events(
     mut commands: Commands,
     mut events: ResMut<Events<BlockDestroyEvent>>,
     mut em_world: ResMut<ExcavateManufacturateWorld>,
+    block_registry: Res<BlockRegistryResource>,
     chunk_spawn_queue: Res<ChunkSpawnQueue>,
 ) {
     for event in events.drain() {
@@ -133,9 +313,32 @@ pub fn apply_block_destroy_events(
             commands.entity(entity).despawn();
         }
 
-        if em_world.set_block(event.pos, BlockData::none()) {
+        if em_world.set_block(event.pos, BlockData::none(), &block_registry) {
             // Redraw chunks if needed
             chunk_spawn_queue.submit_on_block_update(event.pos);
         }
     }
 }
+
+/// Spreads light between newly added chunks and their neighbors. Connecting a chunk spreads light across all of its
+/// sides, so only a few chunks are connected each frame.
+pub fn connect_chunk_light(
+    mut em_world: ResMut<ExcavateManufacturateWorld>,
+    block_registry: Res<BlockRegistryResource>,
+) {
+    em_world.connect_chunks(NUM_CHUNKS_LIT_PER_FRAME, &block_registry);
+}
+
+/// Rebuilds the meshes of chunks whose light changed, including chunks that are still queued or being meshed.
+pub fn remesh_relit_chunks(
+    mut em_world: ResMut<ExcavateManufacturateWorld>,
+    spawned_chunks: Res<SpawnedChunks>,
+    possibly_spawned_chunks: Res<PossiblySpawnedChunks>,
+    chunk_spawn_queue: Res<ChunkSpawnQueue>,
+) {
+    for chunk_pos in em_world.take_relit_chunks() {
+        if spawned_chunks.contains_key(&chunk_pos) || possibly_spawned_chunks.contains(&chunk_pos) {
+            chunk_spawn_queue.push(chunk_pos);
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use bevy::prelude::*;
+use noisy_bevy::simplex_noise_2d;
+
+use crate::{
+    util::block_pos::BlockPos,
+    world::{
+        block::{excavatemanufacturate_blocks::block_names, BlockName},
+        seed::WorldSeed,
+    },
+};
+
+use super::salt;
+
+/// The climate regions of the world. Every column of blocks belongs to one biome, which decides the shape of the
+/// terrain and what it's covered with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Biome {
+    Plains,
+    Forest,
+    Desert,
+    Tundra,
+    Mountains,
+}
+
+/// How a biome generates.
+pub struct BiomeParameters {
+    pub name: &'static str,
+
+    /// The temperature and humidity the biome is found at, from -1 to 1 each. Every column gets the biome whose
+    /// climate is closest to its own.
+    pub climate: Vec2,
+
+    /// The height the ground lies at on average.
+    pub base_height: f32,
+
+    /// How far hills rise above and sink below the base height.
+    pub height_variation: f32,
+
+    /// The top block of the ground.
+    pub surface_block: BlockName,
+
+    /// The blocks below the surface block, down to `subsurface_depth` blocks below it. Stone lies underneath.
+    pub subsurface_block: BlockName,
+    pub subsurface_depth: f32,
+
+    /// A plant that grows on top of the surface block, and the chance for each surface block to have one.
+    pub vegetation: Option<(BlockName, f32)>,
+
+    /// The chance for each column to grow a tree, if its top block is grass.
+    pub tree_chance: f32,
+}
+
+const PLAINS: BiomeParameters = BiomeParameters {
+    name: "Plains",
+    climate: Vec2::new(0.0, 0.0),
+    base_height: 30.0,
+    height_variation: 8.0,
+    surface_block: block_names::GRASS,
+    subsurface_block: block_names::DIRT,
+    subsurface_depth: 4.0,
+    vegetation: Some((block_names::TALL_GRASS, 0.15)),
+    tree_chance: 0.001,
+};
+
+const FOREST: BiomeParameters = BiomeParameters {
+    name: "Forest",
+    climate: Vec2::new(0.1, 0.6),
+    base_height: 32.0,
+    height_variation: 14.0,
+    surface_block: block_names::GRASS,
+    subsurface_block: block_names::DIRT,
+    subsurface_depth: 6.0,
+    vegetation: Some((block_names::TALL_GRASS, 0.3)),
+    tree_chance: 0.012,
+};
+
+const DESERT: BiomeParameters = BiomeParameters {
+    name: "Desert",
+    climate: Vec2::new(0.7, -0.6),
+    base_height: 28.0,
+    height_variation: 6.0,
+    surface_block: block_names::SAND,
+    subsurface_block: block_names::SAND,
+    subsurface_depth: 5.0,
+    vegetation: None,
+    tree_chance: 0.0,
+};
+
+const TUNDRA: BiomeParameters = BiomeParameters {
+    name: "Tundra",
+    climate: Vec2::new(-0.7, 0.0),
+    base_height: 30.0,
+    height_variation: 10.0,
+    surface_block: block_names::SNOW,
+    subsurface_block: block_names::DIRT,
+    subsurface_depth: 3.0,
+    vegetation: None,
+    tree_chance: 0.0,
+};
+
+const MOUNTAINS: BiomeParameters = BiomeParameters {
+    name: "Mountains",
+    climate: Vec2::new(-0.2, -0.6),
+    base_height: 45.0,
+    height_variation: 40.0,
+    surface_block: block_names::STONE,
+    subsurface_block: block_names::STONE,
+    subsurface_depth: 0.0,
+    vegetation: None,
+    tree_chance: 0.0,
+};
+
+impl Biome {
+    pub const ALL: [Biome; 5] = [
+        Biome::Plains,
+        Biome::Forest,
+        Biome::Desert,
+        Biome::Tundra,
+        Biome::Mountains,
+    ];
+
+    pub fn parameters(self) -> &'static BiomeParameters {
+        match self {
+            Biome::Plains => &PLAINS,
+            Biome::Forest => &FOREST,
+            Biome::Desert => &DESERT,
+            Biome::Tundra => &TUNDRA,
+            Biome::Mountains => &MOUNTAINS,
+        }
+    }
+}
+
+/// Decides the climate of every column of blocks, and with it the biome. Depends only on the seed, so biomes are
+/// known everywhere, even where no chunk has been generated yet.
+#[derive(Clone)]
+pub struct BiomeSource {
+    temperature_offset: Vec2,
+    humidity_offset: Vec2,
+}
+
+impl BiomeSource {
+    /// How quickly the climate changes. Biomes span several hundred blocks.
+    const CLIMATE_FREQUENCY: f32 = 0.0015;
+
+    /// How sharply the terrain changes from one biome to the other at their border. Lower values blend the heights
+    /// over a wider area.
+    const BLEND_SHARPNESS: f32 = 40.0;
+
+    pub fn new(seed: WorldSeed) -> Self {
+        Self {
+            temperature_offset: seed.noise_offset(salt::TEMPERATURE).xz(),
+            humidity_offset: seed.noise_offset(salt::HUMIDITY).xz(),
+        }
+    }
+
+    /// The temperature and humidity of the column, from about -1 to 1 each.
+    pub fn climate(&self, x: i32, z: i32) -> Vec2 {
+        let position = Vec2::new(x as f32, z as f32) * Self::CLIMATE_FREQUENCY;
+
+        Vec2::new(
+            simplex_noise_2d(position + self.temperature_offset),
+            simplex_noise_2d(position + self.humidity_offset),
+        )
+    }
+
+    pub fn biome_at(&self, block_pos: BlockPos) -> Biome {
+        let climate = self.climate(block_pos.x, block_pos.z);
+
+        Biome::ALL
+            .into_iter()
+            .min_by(|a, b| {
+                let distance = |biome: &Biome| biome.parameters().climate.distance_squared(climate);
+                distance(a).total_cmp(&distance(b))
+            })
+            .unwrap()
+    }
+
+    /// How much each biome of [`Biome::ALL`] contributes to the terrain at the climate, adding up to 1. The closest
+    /// biome contributes the most, but the others fade in smoothly towards their borders, so the terrain doesn't
+    /// jump where the biome changes.
+    pub fn biome_weights(&self, climate: Vec2) -> [f32; Biome::ALL.len()] {
+        let distances =
+            Biome::ALL.map(|biome| biome.parameters().climate.distance_squared(climate));
+        let closest = distances.into_iter().fold(f32::INFINITY, f32::min);
+
+        let weights =
+            distances.map(|distance| (-(distance - closest) * Self::BLEND_SHARPNESS).exp());
+        let total: f32 = weights.iter().sum();
+
+        weights.map(|weight| weight / total)
+    }
+}
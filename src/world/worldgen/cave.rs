@@ -0,0 +1,285 @@
+use std::{
+    f32::consts::{PI, TAU},
+    ops::RangeInclusive,
+};
+
+use bevy::prelude::*;
+use noisy_bevy::simplex_noise_3d;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::{
+    util::{block_pos::BlockPos, chunk_pos::ChunkPos},
+    world::{
+        chunk::{ChunkData, CHUNK_VOLUME},
+        seed::WorldSeed,
+        CHUNK_SIZE_INT,
+    },
+};
+
+use super::salt;
+
+/// The blocks of a chunk that caves remove from the terrain, by chunk offset.
+pub struct CarveMask {
+    bits: Box<[u64]>,
+}
+
+impl CarveMask {
+    pub fn new() -> Self {
+        Self {
+            bits: vec![0; CHUNK_VOLUME.div_ceil(64)].into_boxed_slice(),
+        }
+    }
+
+    fn is_in_chunk(offset: IVec3) -> bool {
+        offset.cmpge(IVec3::ZERO).all() && offset.cmplt(IVec3::splat(CHUNK_SIZE_INT)).all()
+    }
+
+    /// Whether the block at the chunk offset is carved out. Offsets outside of the chunk never are.
+    pub fn contains(&self, offset: IVec3) -> bool {
+        if !Self::is_in_chunk(offset) {
+            return false;
+        }
+
+        let index = ChunkData::indexify(offset);
+        self.bits[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    fn insert(&mut self, offset: IVec3) {
+        let index = ChunkData::indexify(offset);
+        self.bits[index / 64] |= 1 << (index % 64);
+    }
+}
+
+/// Carves caves out of the terrain. Carvers decide a whole chunk at once, and only from the world seed and the
+/// position of the chunk, so chunks can be carved in any order and on any thread.
+pub trait Carver: Send + Sync {
+    fn carve(&self, chunk_pos: ChunkPos, mask: &mut CarveMask);
+}
+
+/// Large open caverns, wherever 3D noise is above a threshold.
+pub struct CheeseCaveParameters {
+    /// The heights the caverns lie between. They taper off towards both ends.
+    pub min_y: i32,
+    pub max_y: i32,
+
+    /// How often the noise changes per block. Lower frequencies make larger caverns that lie further apart.
+    pub frequency: f32,
+
+    /// The noise value above which blocks are carved, from -1 to 1. Higher thresholds leave more stone.
+    pub threshold: f32,
+}
+
+pub struct CheeseCaves {
+    parameters: CheeseCaveParameters,
+    noise_offset: Vec3,
+}
+
+impl CheeseCaves {
+    /// How many blocks it takes caverns to taper off at the ends of their height range.
+    const TAPER_DISTANCE: f32 = 8.0;
+
+    pub fn new(seed: WorldSeed, parameters: CheeseCaveParameters) -> Self {
+        Self {
+            parameters,
+            noise_offset: seed.noise_offset(salt::CHEESE_CAVES),
+        }
+    }
+}
+
+impl Carver for CheeseCaves {
+    fn carve(&self, chunk_pos: ChunkPos, mask: &mut CarveMask) {
+        let origin = BlockPos::from(chunk_pos);
+        let CheeseCaveParameters {
+            min_y,
+            max_y,
+            frequency,
+            threshold,
+        } = self.parameters;
+
+        if origin.y > max_y || origin.y + CHUNK_SIZE_INT <= min_y {
+            return;
+        }
+
+        for index in 0..CHUNK_VOLUME {
+            let offset = ChunkData::deindexify(index);
+            let y = origin.y + offset.y;
+
+            if !(min_y..=max_y).contains(&y) {
+                continue;
+            }
+
+            // Raise the threshold close to the ends of the range, so caverns don't end in flat floors and ceilings
+            let distance_to_end = (y - min_y).min(max_y - y) as f32;
+            let taper = (1.0 - distance_to_end / Self::TAPER_DISTANCE).max(0.0);
+
+            let position = (origin.inner() + offset).as_vec3();
+            let noise = simplex_noise_3d(position * frequency + self.noise_offset);
+
+            if noise > threshold + taper * (1.0 - threshold) {
+                mask.insert(offset);
+            }
+        }
+    }
+}
+
+/// Long winding tunnels. Every chunk may start tunnels, which wander off in random directions and can carve through
+/// the chunks around it.
+pub struct WormCaveParameters {
+    /// The heights tunnels start between. They wander up and down, but are only carved between these heights, so they
+    /// never cut through the bottom of the world.
+    pub min_y: i32,
+    pub max_y: i32,
+
+    /// How many tunnels start in a chunk on average.
+    pub frequency: f32,
+
+    /// How many blocks a tunnel is long.
+    pub length: u32,
+
+    /// The radius of tunnels, which varies along their length.
+    pub min_radius: f32,
+    pub max_radius: f32,
+}
+
+pub struct WormCaves {
+    parameters: WormCaveParameters,
+    seed: WorldSeed,
+}
+
+impl WormCaves {
+    pub fn new(seed: WorldSeed, parameters: WormCaveParameters) -> Self {
+        Self { parameters, seed }
+    }
+
+    /// How many chunks away from the chunk it starts in a tunnel can reach.
+    fn reach_in_chunks(&self) -> i32 {
+        let reach = self.parameters.length as f32 + self.parameters.max_radius;
+        (reach / CHUNK_SIZE_INT as f32).ceil() as i32
+    }
+
+    /// Follows the tunnels that start in `start_chunk`, and carves the parts of them that lie in the chunk at
+    /// `origin`. The tunnels only depend on the start chunk, so every chunk they pass through sees the same tunnels.
+    fn carve_tunnels_from(&self, start_chunk: ChunkPos, origin: BlockPos, mask: &mut CarveMask) {
+        let parameters = &self.parameters;
+        let start_origin = BlockPos::from(start_chunk);
+
+        // Tunnels have to start in their own chunk, so no tunnel is followed twice
+        let min_start_y = parameters.min_y.max(start_origin.y);
+        let max_start_y = parameters.max_y.min(start_origin.y + CHUNK_SIZE_INT - 1);
+
+        if min_start_y > max_start_y {
+            return;
+        }
+
+        // `StdRng` may change between versions and platforms, which would move the tunnels of existing worlds
+        let mut rng =
+            ChaCha8Rng::seed_from_u64(self.seed.derive_at(salt::WORM_CAVES, start_chunk.inner()));
+
+        // Round the average number of tunnels up or down at random
+        let tunnel_count = parameters.frequency.floor() as u32
+            + (rng.gen::<f32>() < parameters.frequency.fract()) as u32;
+
+        for _ in 0..tunnel_count {
+            let mut position = Vec3::new(
+                (start_origin.x + rng.gen_range(0..CHUNK_SIZE_INT)) as f32,
+                rng.gen_range(min_start_y..=max_start_y) as f32,
+                (start_origin.z + rng.gen_range(0..CHUNK_SIZE_INT)) as f32,
+            );
+
+            let mut yaw: f32 = rng.gen_range(0.0..TAU);
+            let mut pitch: f32 = rng.gen_range(-0.25..0.25);
+            let mut yaw_change: f32 = 0.0;
+            let mut pitch_change: f32 = 0.0;
+            let width = rng.gen_range(parameters.min_radius..=parameters.max_radius);
+
+            for step in 0..parameters.length {
+                // Tunnels are widest in the middle, and narrow towards their ends
+                let progress = step as f32 / parameters.length as f32;
+                let radius =
+                    (width * (0.6 + 0.4 * (progress * PI).sin())).max(parameters.min_radius);
+
+                carve_sphere(
+                    position,
+                    radius,
+                    parameters.min_y..=parameters.max_y,
+                    origin,
+                    mask,
+                );
+
+                position += Vec3::new(
+                    yaw.cos() * pitch.cos(),
+                    pitch.sin(),
+                    yaw.sin() * pitch.cos(),
+                );
+
+                // Turn smoothly, and flatten out over time so tunnels don't dive straight down
+                yaw += yaw_change * 0.1;
+                pitch = pitch * 0.7 + pitch_change * 0.1;
+                yaw_change = yaw_change * 0.9 + rng.gen_range(-1.0..1.0);
+                pitch_change = pitch_change * 0.75 + rng.gen_range(-1.0..1.0);
+            }
+        }
+    }
+}
+
+impl Carver for WormCaves {
+    fn carve(&self, chunk_pos: ChunkPos, mask: &mut CarveMask) {
+        let origin = BlockPos::from(chunk_pos);
+        let reach = self.reach_in_chunks();
+
+        if origin.y > self.parameters.max_y || origin.y + CHUNK_SIZE_INT <= self.parameters.min_y {
+            return;
+        }
+
+        // Only chunks that tunnels can start in, and that are close enough for their tunnels to reach this chunk
+        let min_start_chunk_y = self.parameters.min_y.div_euclid(CHUNK_SIZE_INT);
+        let max_start_chunk_y = self.parameters.max_y.div_euclid(CHUNK_SIZE_INT);
+
+        for x in -reach..=reach {
+            for y in -reach..=reach {
+                for z in -reach..=reach {
+                    let start_chunk = chunk_pos + ChunkPos::new(x, y, z);
+
+                    if (min_start_chunk_y..=max_start_chunk_y).contains(&start_chunk.y) {
+                        self.carve_tunnels_from(start_chunk, origin, mask);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Carves the blocks of the chunk at `origin` whose centers lie inside of the sphere, leaving out the blocks outside of
+/// the range of heights.
+fn carve_sphere(
+    center: Vec3,
+    radius: f32,
+    heights: RangeInclusive<i32>,
+    origin: BlockPos,
+    mask: &mut CarveMask,
+) {
+    let chunk_min = origin.inner();
+    let chunk_max = chunk_min + CHUNK_SIZE_INT - 1;
+
+    let mut min = (center - radius).floor().as_ivec3().max(chunk_min);
+    let mut max = (center + radius).ceil().as_ivec3().min(chunk_max);
+    min.y = min.y.max(*heights.start());
+    max.y = max.y.min(*heights.end());
+
+    if min.cmpgt(max).any() {
+        return;
+    }
+
+    for x in min.x..=max.x {
+        for y in min.y..=max.y {
+            for z in min.z..=max.z {
+                let block = IVec3::new(x, y, z);
+
+                if (block.as_vec3() + 0.5).distance_squared(center) <= radius * radius {
+                    mask.insert(block - chunk_min);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use bevy::utils::HashMap;
+
+use crate::{
+    util::{block_pos::BlockPos, chunk_pos::ChunkPos},
+    world::{
+        block::{registry::BlockRegistry, BlockData},
+        chunk::ChunkData,
+    },
+};
+
+/// Something larger than a single block that's placed into the finished terrain, like a tree. Features start in a
+/// chunk, but can reach into the chunks around it.
+pub trait Feature: Send + Sync {
+    /// Places the feature wherever it starts in the chunk. Only the blocks of the chunk itself can be looked at, but
+    /// blocks can be placed anywhere. Anything random has to be derived from the seed and the position the feature
+    /// starts at, so a feature comes out the same no matter in which order chunks are generated.
+    fn place(
+        &self,
+        chunk_pos: ChunkPos,
+        chunk_data: &ChunkData,
+        registry: &BlockRegistry,
+        blocks: &mut FeatureBlocks,
+    );
+}
+
+/// Whether a feature block may replace the block. Features only replace empty blocks and blocks that let light
+/// through, like plants and leaves, so they never cut into the terrain. Since a trunk replaces leaves but leaves don't
+/// replace a trunk, overlapping features come out the same no matter which of them is placed first.
+pub fn can_replace(block_data: &BlockData, registry: &BlockRegistry) -> bool {
+    match block_data.as_ref() {
+        Some(block) => registry.get_block_data(block.id).transparent,
+        None => true,
+    }
+}
+
+/// The blocks placed by the features of a chunk, grouped by the chunk they land in. Blocks in other chunks are
+/// placed once those chunks are generated, or right away if they already are.
+#[derive(Default)]
+pub struct FeatureBlocks {
+    chunks: HashMap<ChunkPos, Vec<(BlockPos, BlockData)>>,
+}
+
+impl FeatureBlocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Places a block, see [`can_replace`] for which blocks it replaces. Blocks are placed in the order they're set.
+    pub fn set(&mut self, block_pos: BlockPos, block: BlockData) {
+        self.chunks
+            .entry(ChunkPos::from(block_pos))
+            .or_default()
+            .push((block_pos, block));
+    }
+
+    /// Removes and returns the blocks that land in the chunk.
+    pub fn take_chunk(&mut self, chunk_pos: ChunkPos) -> Vec<(BlockPos, BlockData)> {
+        self.chunks.remove(&chunk_pos).unwrap_or_default()
+    }
+
+    /// The blocks by the chunk they land in.
+    pub fn into_chunks(self) -> impl Iterator<Item = (ChunkPos, Vec<(BlockPos, BlockData)>)> {
+        self.chunks.into_iter()
+    }
+}
+
+/// Places the blocks of features that land in a chunk which isn't in the world yet.
+pub fn place_in_chunk(
+    chunk_data: &mut ChunkData,
+    blocks: Vec<(BlockPos, BlockData)>,
+    registry: &BlockRegistry,
+) {
+    for (block_pos, block) in blocks {
+        if can_replace(chunk_data.get(block_pos), registry) {
+            chunk_data.set(block_pos, block);
+        }
+    }
+}
@@ -0,0 +1,383 @@
+use std::sync::Arc;
+
+use bevy::prelude::*;
+
+use crate::{
+    mods::ModContent,
+    util::{block_pos::BlockPos, chunk_pos::ChunkPos},
+    world::block,
+};
+
+use self::{
+    biome::{Biome, BiomeSource},
+    cave::{CarveMask, Carver, CheeseCaveParameters, CheeseCaves, WormCaveParameters, WormCaves},
+    feature::{Feature, FeatureBlocks},
+    ore::{OreFeature, OreParameters},
+    tree::{TreeParameters, Trees},
+};
+
+use super::{
+    block::{registry::BlockRegistry, BlockData},
+    chunk::ChunkData,
+    save::ActiveWorldSave,
+    seed::WorldSeed,
+    CHUNK_SIZE, CHUNK_SIZE_INT,
+};
+
+pub mod biome;
+pub mod cave;
+pub mod feature;
+pub mod ore;
+pub mod tree;
+
+/// The salts that separate the uses of the world seed in generation, see [`WorldSeed::derive`]. Every noise and
+/// random choice has its own, so they don't line up with each other.
+pub mod salt {
+    pub const HILLS: u64 = 0;
+    pub const HILLS_MULTIPLIER: u64 = 1;
+    pub const TEMPERATURE: u64 = 2;
+    pub const HUMIDITY: u64 = 3;
+    pub const VEGETATION: u64 = 4;
+    pub const CHEESE_CAVES: u64 = 5;
+    pub const WORM_CAVES: u64 = 6;
+
+    /// Shared by all ores, which tell themselves apart by their index, see [`super::ore::OreFeature::new`].
+    pub const ORES: u64 = 7;
+
+    pub const TREES: u64 = 8;
+}
+
+/// Where the column at the x and z offsets inside of a chunk is found in per-column arrays.
+fn column_index(x: i32, z: i32) -> usize {
+    z as usize * CHUNK_SIZE + x as usize
+}
+
+pub trait WorldGenerator {
+    /// The shape of the terrain in a chunk, before the surface is built on top of it.
+    fn terrain_noise(&self, chunk_pos: ChunkPos, registry: &BlockRegistry) -> ChunkData;
+
+    /// Covers the shaped terrain of a chunk with the blocks and plants of its biomes.
+    fn build_surface(
+        &self,
+        chunk_pos: ChunkPos,
+        chunk_data: &mut ChunkData,
+        registry: &BlockRegistry,
+    );
+
+    /// Decides which blocks of the chunk's terrain are carved out for caves.
+    fn carve(&self, chunk_pos: ChunkPos) -> CarveMask;
+
+    /// Adds everything that's placed into the finished terrain of a chunk, like ores.
+    fn populate(&self, chunk_pos: ChunkPos, chunk_data: &mut ChunkData, registry: &BlockRegistry);
+
+    /// Places the features that start in the chunk, like trees, once its terrain is done. Their blocks can land in
+    /// other chunks, see [`FeatureBlocks`].
+    fn place_features(
+        &self,
+        chunk_pos: ChunkPos,
+        chunk_data: &ChunkData,
+        registry: &BlockRegistry,
+    ) -> FeatureBlocks;
+}
+
+/// Generation that runs on top of the terrain, such as ores or decorations. Mods register features through
+/// [`crate::mods::ModDefinition::with_world_gen_feature`], and they run in registration order.
+pub trait WorldGenFeature: Send + Sync {
+    /// Decides the block at a position, given the block that was generated there so far. Anything random has to be
+    /// derived from the seed and the position, so every world is generated the same way each time.
+    fn generate(
+        &self,
+        block_pos: BlockPos,
+        block: BlockData,
+        seed: WorldSeed,
+        registry: &BlockRegistry,
+    ) -> BlockData;
+}
+
+/// Every registered world generation feature. Fixed once the mods have been loaded.
+#[derive(Resource, Deref, Clone)]
+pub struct WorldGenFeatures(Arc<[Box<dyn WorldGenFeature>]>);
+
+impl WorldGenFeatures {
+    /// Runs every feature on a block the terrain generator produced.
+    pub fn generate(
+        &self,
+        block_pos: BlockPos,
+        block: BlockData,
+        seed: WorldSeed,
+        registry: &BlockRegistry,
+    ) -> BlockData {
+        self.iter().fold(block, |block, feature| {
+            feature.generate(block_pos, block, seed, registry)
+        })
+    }
+}
+
+pub fn init_features(mut commands: Commands, mut mod_content: ResMut<ModContent>) {
+    let features: Vec<_> = std::mem::take(&mut mod_content.world_gen_features);
+    info!("Registered {} world generation features", features.len());
+
+    commands.insert_resource(WorldGenFeatures(features.into()));
+}
+
+pub struct OverworldGenerator {
+    seed: WorldSeed,
+    biome_source: BiomeSource,
+
+    /// Where this world samples the noise that shapes its hills.
+    hills_offset: Vec2,
+
+    /// Where this world samples the noise that decides how tall the hills are.
+    hills_multiplier_offset: Vec2,
+
+    carvers: Vec<Box<dyn Carver>>,
+    ores: Vec<OreFeature>,
+    features: Vec<Box<dyn Feature>>,
+}
+
+impl OverworldGenerator {
+    pub fn new(seed: WorldSeed) -> Self {
+        use block::excavatemanufacturate_blocks::block_names::*;
+
+        Self {
+            seed,
+            biome_source: BiomeSource::new(seed),
+            hills_offset: seed.noise_offset(salt::HILLS).xz(),
+            hills_multiplier_offset: seed.noise_offset(salt::HILLS_MULTIPLIER).xz(),
+            carvers: vec![
+                Box::new(CheeseCaves::new(
+                    seed,
+                    CheeseCaveParameters {
+                        min_y: 1,
+                        max_y: 24,
+                        frequency: 0.04,
+                        threshold: 0.55,
+                    },
+                )),
+                Box::new(WormCaves::new(
+                    seed,
+                    WormCaveParameters {
+                        min_y: 4,
+                        max_y: 40,
+                        frequency: 0.3,
+                        length: 96,
+                        min_radius: 1.5,
+                        max_radius: 3.5,
+                    },
+                )),
+            ],
+            ores: [
+                OreParameters {
+                    ore: COAL_ORE,
+                    hosts: vec![STONE],
+                    min_y: 1,
+                    max_y: 80,
+                    vein_size: 12,
+                    veins_per_chunk: 10.0,
+                },
+                OreParameters {
+                    ore: COPPER_ORE,
+                    hosts: vec![STONE],
+                    min_y: 1,
+                    max_y: 48,
+                    vein_size: 8,
+                    veins_per_chunk: 6.0,
+                },
+                OreParameters {
+                    ore: IRON_ORE,
+                    hosts: vec![STONE],
+                    min_y: 1,
+                    max_y: 36,
+                    vein_size: 6,
+                    veins_per_chunk: 4.0,
+                },
+            ]
+            .into_iter()
+            .enumerate()
+            .map(|(index, parameters)| OreFeature::new(seed, index as u64, parameters))
+            .collect(),
+            features: vec![Box::new(Trees::new(
+                seed,
+                BiomeSource::new(seed),
+                TreeParameters {
+                    trunk: LOG,
+                    leaves: LEAVES,
+                    soil: GRASS,
+                    min_trunk_height: 4,
+                    max_trunk_height: 6,
+                },
+            ))],
+        }
+    }
+
+    /// The height of the ground in the column. Every biome shapes the hills its own way, and the heights of the
+    /// biomes are blended by how close the column's climate is to each of them.
+    fn ground_height(&self, x: i32, z: i32) -> f32 {
+        use noisy_bevy::*;
+
+        let position = Vec2::new(x as f32, z as f32);
+
+        let hills_multiplier =
+            simplex_noise_2d(position * 0.005 + self.hills_multiplier_offset) * 0.5 + 0.5;
+        let hills = hills_multiplier * simplex_noise_2d(position * 0.025 + self.hills_offset);
+
+        let weights = self
+            .biome_source
+            .biome_weights(self.biome_source.climate(x, z));
+
+        Biome::ALL
+            .into_iter()
+            .zip(weights)
+            .map(|(biome, weight)| {
+                let parameters = biome.parameters();
+                weight * (parameters.base_height + parameters.height_variation * hills)
+            })
+            .sum()
+    }
+
+    /// The height of the ground in every column of the chunk, see [`Self::ground_height`], by [`column_index`]. It
+    /// only depends on the column, so it's computed once for all the blocks above each other.
+    fn ground_heights(&self, chunk_pos: ChunkPos) -> Box<[f32]> {
+        let origin = BlockPos::from(chunk_pos);
+        let mut heights = vec![0.0; CHUNK_SIZE * CHUNK_SIZE].into_boxed_slice();
+
+        for x in 0..CHUNK_SIZE_INT {
+            for z in 0..CHUNK_SIZE_INT {
+                heights[column_index(x, z)] = self.ground_height(origin.x + x, origin.z + z);
+            }
+        }
+
+        heights
+    }
+}
+
+impl WorldGenerator for OverworldGenerator {
+    fn terrain_noise(&self, chunk_pos: ChunkPos, registry: &BlockRegistry) -> ChunkData {
+        use block::excavatemanufacturate_blocks::block_names::*;
+
+        let origin = BlockPos::from(chunk_pos);
+        let ground_heights = self.ground_heights(chunk_pos);
+
+        let bedrock = BlockData::some(registry.create_block(&BEDROCK).unwrap());
+        let stone = BlockData::some(registry.create_block(&STONE).unwrap());
+
+        ChunkData::with_data(|offset| {
+            let y = origin.y + offset.y;
+
+            match y.cmp(&0) {
+                std::cmp::Ordering::Less => BlockData::none(),
+                // The bottom of the world is bedrock, the ground of the biomes lies on top of it
+                std::cmp::Ordering::Equal => bedrock.clone(),
+                std::cmp::Ordering::Greater => {
+                    if ground_heights[column_index(offset.x, offset.z)] > y as f32 {
+                        stone.clone()
+                    } else {
+                        BlockData::none()
+                    }
+                }
+            }
+        })
+    }
+
+    fn build_surface(
+        &self,
+        chunk_pos: ChunkPos,
+        chunk_data: &mut ChunkData,
+        registry: &BlockRegistry,
+    ) {
+        let origin = BlockPos::from(chunk_pos);
+        let ground_heights = self.ground_heights(chunk_pos);
+
+        for x in 0..CHUNK_SIZE_INT {
+            for z in 0..CHUNK_SIZE_INT {
+                let (world_x, world_z) = (origin.x + x, origin.z + z);
+
+                let biome = self
+                    .biome_source
+                    .biome_at(BlockPos::new(world_x, 0, world_z))
+                    .parameters();
+                let ground_height = ground_heights[column_index(x, z)];
+
+                // The bottom of the world is left as it is
+                for y in 0.max(1 - origin.y)..CHUNK_SIZE_INT {
+                    let block_pos = BlockPos::new(world_x, origin.y + y, world_z);
+
+                    // How far the block lies below the top of the ground, the top block being between 0 and 1
+                    let depth = ground_height - block_pos.y as f32;
+
+                    let block_name = if depth > 1.0 + biome.subsurface_depth {
+                        continue;
+                    } else if depth > 1.0 {
+                        &biome.subsurface_block
+                    } else if depth > 0.0 {
+                        &biome.surface_block
+                    } else if depth > -1.0 {
+                        // Right above the ground, where plants grow
+                        match &biome.vegetation {
+                            Some((plant, chance))
+                                if self.seed.random_at(salt::VEGETATION, block_pos.inner())
+                                    < *chance =>
+                            {
+                                plant
+                            }
+                            _ => continue,
+                        }
+                    } else {
+                        break;
+                    };
+
+                    chunk_data.set(
+                        block_pos,
+                        BlockData::some(registry.create_block(block_name).unwrap()),
+                    );
+                }
+            }
+        }
+    }
+
+    fn carve(&self, chunk_pos: ChunkPos) -> CarveMask {
+        let mut mask = CarveMask::new();
+
+        for carver in self.carvers.iter() {
+            carver.carve(chunk_pos, &mut mask);
+        }
+
+        mask
+    }
+
+    fn populate(&self, chunk_pos: ChunkPos, chunk_data: &mut ChunkData, registry: &BlockRegistry) {
+        for ore in self.ores.iter() {
+            ore.place(chunk_pos, chunk_data, registry);
+        }
+    }
+
+    fn place_features(
+        &self,
+        chunk_pos: ChunkPos,
+        chunk_data: &ChunkData,
+        registry: &BlockRegistry,
+    ) -> FeatureBlocks {
+        let mut blocks = FeatureBlocks::new();
+
+        for feature in self.features.iter() {
+            feature.place(chunk_pos, chunk_data, registry, &mut blocks);
+        }
+
+        blocks
+    }
+}
+
+#[derive(Resource, Deref, DerefMut)]
+pub struct ActiveWorldGenerator<T: WorldGenerator>(Arc<T>);
+
+pub fn setup(mut commands: Commands, world_save: Res<ActiveWorldSave>) {
+    commands.insert_resource(ActiveWorldGenerator(Arc::new(OverworldGenerator::new(
+        world_save.seed(),
+    ))));
+    info!("Set up world generator");
+}
+
+pub fn cleanup(mut commands: Commands) {
+    commands.remove_resource::<ActiveWorldGenerator<OverworldGenerator>>();
+    info!("Cleaned up world generator");
+}
@@ -0,0 +1,144 @@
+use bevy::prelude::*;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::{
+    util::{block_pos::BlockPos, chunk_pos::ChunkPos},
+    world::{
+        block::{registry::BlockRegistry, BlockData, BlockName},
+        chunk::ChunkData,
+        seed::WorldSeed,
+        CHUNK_SIZE, CHUNK_SIZE_INT,
+    },
+};
+
+use super::salt;
+
+/// How an ore is spread through the world.
+pub struct OreParameters {
+    pub ore: BlockName,
+
+    /// The blocks that veins of the ore can replace. Veins leave every other block alone, so they never float in
+    /// caves or stick out of the ground.
+    pub hosts: Vec<BlockName>,
+
+    /// The heights veins start between.
+    pub min_y: i32,
+    pub max_y: i32,
+
+    /// How many blocks a vein has at most.
+    pub vein_size: u32,
+
+    /// How many veins start in a chunk on average. Rare ores have less than one.
+    pub veins_per_chunk: f32,
+}
+
+/// Places veins of an ore. Veins are seeded by the chunk they start in, and can reach into the chunks around it.
+pub struct OreFeature {
+    parameters: OreParameters,
+    seed: WorldSeed,
+
+    /// Separates the veins of this ore from the veins of the other ores.
+    salt: u64,
+}
+
+impl OreFeature {
+    /// Every ore of a world needs its own `index`, so the veins of different ores don't start in the same spots.
+    pub fn new(seed: WorldSeed, index: u64, parameters: OreParameters) -> Self {
+        Self {
+            parameters,
+            seed,
+            salt: salt::ORES.wrapping_add(index << 32),
+        }
+    }
+
+    /// Places the parts of the ore's veins that lie inside of the chunk.
+    pub fn place(
+        &self,
+        chunk_pos: ChunkPos,
+        chunk_data: &mut ChunkData,
+        block_registry: &BlockRegistry,
+    ) {
+        let Some(ore) = block_registry.create_block(&self.parameters.ore) else {
+            warn!("Ore {} doesn't exist", self.parameters.ore.0);
+            return;
+        };
+
+        let hosts: Vec<_> = self
+            .parameters
+            .hosts
+            .iter()
+            .filter_map(|host| block_registry.create_block(host))
+            .map(|host| host.id)
+            .collect();
+
+        let is_host = |block_data: &BlockData| {
+            block_data.as_ref().is_some_and(|block| {
+                hosts
+                    .iter()
+                    .any(|&host| block_registry.is_same_block(block.id, host))
+            })
+        };
+
+        // A vein can't wander further from where it starts than its size
+        let reach = self.parameters.vein_size.div_ceil(CHUNK_SIZE as u32) as i32;
+        let chunk_min = BlockPos::from(chunk_pos).inner();
+
+        for x in -reach..=reach {
+            for y in -reach..=reach {
+                for z in -reach..=reach {
+                    for block_pos in self.veins_from(chunk_pos + ChunkPos::new(x, y, z)) {
+                        let offset = block_pos - chunk_min;
+                        let in_chunk = offset.cmpge(IVec3::ZERO).all()
+                            && offset.cmplt(IVec3::splat(CHUNK_SIZE_INT)).all();
+
+                        if in_chunk && is_host(chunk_data.get(BlockPos::from(block_pos))) {
+                            chunk_data.set(BlockPos::from(block_pos), BlockData::some(ore.clone()));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// The blocks of the veins that start in the chunk. Every vein wanders from its start one block at a time.
+    fn veins_from(&self, start_chunk: ChunkPos) -> Vec<IVec3> {
+        let parameters = &self.parameters;
+        let start_origin = BlockPos::from(start_chunk);
+
+        // Veins have to start in their own chunk, so no vein is placed twice
+        let min_start_y = parameters.min_y.max(start_origin.y);
+        let max_start_y = parameters.max_y.min(start_origin.y + CHUNK_SIZE_INT - 1);
+
+        if min_start_y > max_start_y {
+            return Vec::new();
+        }
+
+        let mut rng =
+            ChaCha8Rng::seed_from_u64(self.seed.derive_at(self.salt, start_chunk.inner()));
+
+        // Round the average number of veins up or down at random
+        let vein_count = parameters.veins_per_chunk.floor() as u32
+            + (rng.gen::<f32>() < parameters.veins_per_chunk.fract()) as u32;
+
+        let mut blocks = Vec::new();
+
+        for _ in 0..vein_count {
+            let mut position = IVec3::new(
+                start_origin.x + rng.gen_range(0..CHUNK_SIZE_INT),
+                rng.gen_range(min_start_y..=max_start_y),
+                start_origin.z + rng.gen_range(0..CHUNK_SIZE_INT),
+            );
+
+            for _ in 0..rng.gen_range(1..=parameters.vein_size) {
+                blocks.push(position);
+
+                let mut step = IVec3::ZERO;
+                step[rng.gen_range(0..3)] = if rng.gen() { 1 } else { -1 };
+                position += step;
+            }
+        }
+
+        blocks
+    }
+}
@@ -0,0 +1,155 @@
+use bevy::prelude::*;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::{
+    util::{block_pos::BlockPos, chunk_pos::ChunkPos},
+    world::{
+        block::{registry::BlockRegistry, BlockData, BlockName},
+        chunk::ChunkData,
+        heightmap,
+        seed::WorldSeed,
+        CHUNK_SIZE_INT,
+    },
+};
+
+use super::{
+    biome::BiomeSource,
+    feature::{Feature, FeatureBlocks},
+    salt,
+};
+
+/// What trees are made of and how tall they grow.
+pub struct TreeParameters {
+    pub trunk: BlockName,
+    pub leaves: BlockName,
+
+    /// The block trees grow on. Trees only grow where it's the top solid block of the column.
+    pub soil: BlockName,
+
+    /// How many blocks the trunk is tall.
+    pub min_trunk_height: i32,
+    pub max_trunk_height: i32,
+}
+
+/// Trees on the surface, as often as the biome of each column allows, see
+/// [`super::biome::BiomeParameters::tree_chance`]. Their canopies reach into the chunks around the trunk.
+pub struct Trees {
+    parameters: TreeParameters,
+    seed: WorldSeed,
+    biome_source: BiomeSource,
+}
+
+impl Trees {
+    pub fn new(seed: WorldSeed, biome_source: BiomeSource, parameters: TreeParameters) -> Self {
+        Self {
+            parameters,
+            seed,
+            biome_source,
+        }
+    }
+
+    /// Finds the top solid block of the column inside of the chunk, as the offset from the bottom of the chunk.
+    fn ground(chunk_data: &ChunkData, x: i32, z: i32, registry: &BlockRegistry) -> Option<i32> {
+        (0..CHUNK_SIZE_INT).rev().find(|&y| {
+            heightmap::is_solid(
+                chunk_data.get_from_raw_offset(IVec3::new(x, y, z)),
+                registry,
+            )
+        })
+    }
+
+    /// Places a tree whose trunk stands on the block at `ground`.
+    fn place_tree(
+        &self,
+        ground: BlockPos,
+        trunk: &BlockData,
+        leaves: &BlockData,
+        blocks: &mut FeatureBlocks,
+    ) {
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed.derive_at(salt::TREES, ground.inner()));
+        let height =
+            rng.gen_range(self.parameters.min_trunk_height..=self.parameters.max_trunk_height);
+
+        // Two wide layers around the top of the trunk, and two narrow ones above them
+        for y in height - 2..=height + 1 {
+            let radius: i32 = if y < height { 2 } else { 1 };
+
+            for x in -radius..=radius {
+                for z in -radius..=radius {
+                    let corner = x.abs() == radius && z.abs() == radius;
+
+                    // Corners of the top layer are always left out, other corners at random
+                    if corner && (y == height + 1 || rng.gen_bool(0.5)) {
+                        continue;
+                    }
+
+                    blocks.set(
+                        BlockPos::from(ground.inner() + IVec3::new(x, y, z)),
+                        leaves.clone(),
+                    );
+                }
+            }
+        }
+
+        for y in 1..=height {
+            blocks.set(
+                BlockPos::from(ground.inner() + IVec3::new(0, y, 0)),
+                trunk.clone(),
+            );
+        }
+    }
+}
+
+impl Feature for Trees {
+    fn place(
+        &self,
+        chunk_pos: ChunkPos,
+        chunk_data: &ChunkData,
+        registry: &BlockRegistry,
+        blocks: &mut FeatureBlocks,
+    ) {
+        let (Some(trunk), Some(leaves), Some(soil)) = (
+            registry.create_block(&self.parameters.trunk),
+            registry.create_block(&self.parameters.leaves),
+            registry.get_block_id(&self.parameters.soil),
+        ) else {
+            warn!("Tree blocks don't exist");
+            return;
+        };
+
+        let trunk = BlockData::some(trunk);
+        let leaves = BlockData::some(leaves);
+        let origin = BlockPos::from(chunk_pos);
+
+        for x in 0..CHUNK_SIZE_INT {
+            for z in 0..CHUNK_SIZE_INT {
+                let column = IVec3::new(origin.x + x, 0, origin.z + z);
+                let tree_chance = self
+                    .biome_source
+                    .biome_at(BlockPos::from(column))
+                    .parameters()
+                    .tree_chance;
+
+                // Decided per column, so it doesn't matter which chunk of the column the ground is found in
+                if self.seed.random_at(salt::TREES, column) >= tree_chance {
+                    continue;
+                }
+
+                let Some(y) = Self::ground(chunk_data, x, z, registry) else {
+                    continue;
+                };
+
+                let is_soil = chunk_data
+                    .get_from_raw_offset(IVec3::new(x, y, z))
+                    .as_ref()
+                    .is_some_and(|block| registry.is_same_block(block.id, soil));
+
+                if is_soil {
+                    let ground = BlockPos::from(origin.inner() + IVec3::new(x, y, z));
+                    self.place_tree(ground, &trunk, &leaves, blocks);
+                }
+            }
+        }
+    }
+}